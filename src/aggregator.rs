@@ -0,0 +1,452 @@
+use crate::models::{
+    Alignment, Asset, AssetPair, Candlestick, CandlestickData, Duration, Interval, OrderFilled,
+    Time,
+};
+use crate::{Error, Result};
+use std::iter;
+
+/// What is reported for buckets without any filled order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GapFill {
+    /// candlestick without data.
+    Empty,
+    /// flat candlestick at the previous close with zero volume.
+    /// Buckets before the first filled order are still reported without data.
+    CarryForward,
+    /// nothing is reported.
+    Skip,
+}
+
+/// How often the candlestick in progress is reported while filled orders come in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum UpdateMode {
+    /// only closed candlesticks are reported.
+    Disabled,
+    EveryTrade,
+    /// at most one update per duration of trade time.
+    /// The first filled order of a candlestick is always reported.
+    Throttle(Duration),
+}
+
+#[derive(Debug, Clone)]
+pub enum CandlestickEvent<A: Asset> {
+    /// provisional candlestick in progress, later events may still change it.
+    Update(Candlestick<A>),
+    /// closed candlestick.
+    Final(Candlestick<A>),
+}
+
+/// Builds candlesticks of a single asset pair and interval from pushed events,
+/// without owning a thread or channels.
+/// Every method returns the candlesticks closed by the event.
+pub struct CandlestickAggregator<A: Asset> {
+    asset_pair: AssetPair<A>,
+    interval: Interval,
+    alignment: Alignment,
+    current: Option<Candlestick<A>>,
+    update_mode: UpdateMode,
+    last_update: Option<Time>,
+    gap_fill: GapFill,
+    /// flat data at the last close, used to fill gaps.
+    carried: Option<CandlestickData>,
+}
+
+impl<A: Asset> CandlestickAggregator<A> {
+    pub fn new(asset_pair: AssetPair<A>, interval: Interval) -> Self {
+        Self {
+            asset_pair,
+            interval,
+            alignment: Alignment::default(),
+            current: None,
+            update_mode: UpdateMode::Disabled,
+            last_update: None,
+            gap_fill: GapFill::Empty,
+            carried: None,
+        }
+    }
+
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.gap_fill = gap_fill;
+        self
+    }
+
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    /// time zone of the day, week, month and year buckets, UTC by default.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn asset_pair(&self) -> &AssetPair<A> {
+        &self.asset_pair
+    }
+
+    pub fn interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn alignment(&self) -> &Alignment {
+        &self.alignment
+    }
+
+    /// open time of the bucket containing the time.
+    ///
+    /// panics when the bucket starts out of range, see `checked_floor`.
+    pub fn floor(&self, time: &Time) -> Time {
+        self.interval.floor_aligned(time, &self.alignment)
+    }
+
+    /// `Error::TimeOutOfRange` when the bucket starts out of range.
+    pub fn checked_floor(&self, time: &Time) -> Result<Time> {
+        self.interval.checked_floor_aligned(time, &self.alignment)
+    }
+
+    /// candlestick in progress.
+    pub fn current(&self) -> Option<&Candlestick<A>> {
+        self.current.as_ref()
+    }
+
+    pub fn open_time(&self) -> Option<Time> {
+        self.current.as_ref().map(|current| current.open_time)
+    }
+
+    /// apply the filled order, the aggregator is left untouched on error.
+    pub fn on_trade(&mut self, order_filled: &OrderFilled<A>) -> Result<Vec<Candlestick<A>>> {
+        self.validate_trade(order_filled)?;
+        self.trade(order_filled)
+    }
+
+    /// same as `on_trade`, but the candlestick in progress is also reported according to the update mode.
+    pub fn on_trade_events(
+        &mut self,
+        order_filled: &OrderFilled<A>,
+    ) -> Result<Vec<CandlestickEvent<A>>> {
+        let mut events: Vec<CandlestickEvent<A>> = self
+            .on_trade(order_filled)?
+            .into_iter()
+            .map(CandlestickEvent::Final)
+            .collect();
+        events.extend(self.update(order_filled));
+        Ok(events)
+    }
+
+    /// candlestick in progress right after the filled order was applied, if an update is due.
+    pub(crate) fn update(&mut self, order_filled: &OrderFilled<A>) -> Option<CandlestickEvent<A>> {
+        let current = self.current.as_ref()?;
+        let is_due = match (self.update_mode, self.last_update) {
+            (UpdateMode::Disabled, _) => false,
+            (UpdateMode::EveryTrade, _) | (UpdateMode::Throttle(_), None) => true,
+            (UpdateMode::Throttle(duration), Some(last_update)) => {
+                last_update < current.open_time
+                    || last_update.saturating_add(duration) <= order_filled.time
+            }
+        };
+        if !is_due {
+            return None;
+        }
+        self.last_update = Some(order_filled.time);
+        Some(CandlestickEvent::Update(current.clone()))
+    }
+
+    /// start a new candlestick, the aggregator is left untouched on error.
+    pub fn on_tick(&mut self, open_time: Time) -> Result<Vec<Candlestick<A>>> {
+        self.validate_tick(open_time)?;
+        Ok(self.tick(open_time))
+    }
+
+    /// close the candlestick in progress.
+    pub fn flush(&mut self) -> Vec<Candlestick<A>> {
+        let closed = self.current.take().into_iter().collect();
+        self.close(closed)
+    }
+
+    pub(crate) fn validate_tick(&self, open_time: Time) -> Result<()> {
+        if !self
+            .interval
+            .is_valid_time_aligned(&open_time, &self.alignment)
+        {
+            return Err(Error::MisalignedTime {
+                interval: self.interval,
+                time: open_time,
+            });
+        }
+        match self.open_time() {
+            Some(current) if current >= open_time => Err(Error::RegressingTime {
+                current,
+                time: open_time,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn validate_trade(&self, order_filled: &OrderFilled<A>) -> Result<()> {
+        if order_filled.asset_pair != self.asset_pair {
+            return Err(Error::asset_pair_mismatch(
+                &self.asset_pair,
+                &order_filled.asset_pair,
+            ));
+        }
+        let open_time = self.checked_floor(&order_filled.time)?;
+        match self.open_time() {
+            Some(current) if open_time < current => Err(Error::RegressingTime {
+                current,
+                time: open_time,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// must be validated by `validate_tick` beforehand.
+    pub(crate) fn tick(&mut self, open_time: Time) -> Vec<Candlestick<A>> {
+        let new = Candlestick::new(self.asset_pair.clone(), open_time, self.interval);
+        let closed = match self.current.replace(new) {
+            Some(current) => self.gap(current, open_time),
+            None => vec![],
+        };
+        self.close(closed)
+    }
+
+    /// must be validated by `validate_trade` beforehand.
+    /// Only fails on arithmetic overflow, the aggregator is left untouched then.
+    pub(crate) fn trade(&mut self, order_filled: &OrderFilled<A>) -> Result<Vec<Candlestick<A>>> {
+        let data = self.prepare_trade(order_filled)?;
+        Ok(self.commit_trade(order_filled, data))
+    }
+
+    /// data of the bucket of the filled order once it is applied, without applying it.
+    /// must be validated by `validate_trade` beforehand, only fails on arithmetic overflow.
+    pub(crate) fn prepare_trade(&self, order_filled: &OrderFilled<A>) -> Result<CandlestickData> {
+        let open_time = self.floor(&order_filled.time);
+        let data = self
+            .current
+            .as_ref()
+            .filter(|current| current.open_time == open_time)
+            .and_then(|current| current.data.as_ref());
+        match data {
+            Some(data) => {
+                let mut data = data.clone();
+                data.add_order_filled(order_filled)?;
+                Ok(data)
+            }
+            None => CandlestickData::new_with_order_filled(order_filled),
+        }
+    }
+
+    /// stores the data prepared by `prepare_trade` for the same filled order.
+    pub(crate) fn commit_trade(
+        &mut self,
+        order_filled: &OrderFilled<A>,
+        data: CandlestickData,
+    ) -> Vec<Candlestick<A>> {
+        let open_time = self.floor(&order_filled.time);
+        match &mut self.current {
+            Some(current) if current.open_time == open_time => {
+                // update exact the same open_time data
+                current.data = Some(data);
+                vec![]
+            }
+            _ => {
+                let new = Candlestick::new_with_data(
+                    self.asset_pair.clone(),
+                    open_time,
+                    self.interval,
+                    Some(data),
+                );
+                let closed = match self.current.replace(new) {
+                    Some(current) => self.gap(current, open_time),
+                    None => vec![],
+                };
+                self.close(closed)
+            }
+        }
+    }
+
+    /// closed candlestick followed by empty ones for every bucket until the open time.
+    fn gap(&self, closed: Candlestick<A>, open_time: Time) -> Vec<Candlestick<A>> {
+        let next_time = self
+            .interval
+            .next_aligned(closed.open_time, &self.alignment);
+        let gap = self
+            .interval
+            .range_aligned(next_time, open_time, &self.alignment)
+            .map(|next_time| Candlestick::new(self.asset_pair.clone(), next_time, self.interval));
+        iter::once(closed).chain(gap).collect()
+    }
+
+    /// apply the gap fill to the closed candlesticks.
+    fn close(&mut self, closed: Vec<Candlestick<A>>) -> Vec<Candlestick<A>> {
+        let mut results = Vec::with_capacity(closed.len());
+        for mut candlestick in closed {
+            match &candlestick.data {
+                Some(data) => self.carried = Some(data.carry_forward()),
+                None => match self.gap_fill {
+                    GapFill::Empty => {}
+                    GapFill::CarryForward => candlestick.data = self.carried.clone(),
+                    GapFill::Skip => continue,
+                },
+            }
+            results.push(candlestick);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{asset_pair, order_filled};
+
+    #[test]
+    fn test_aggregator() {
+        let mut aggregator = CandlestickAggregator::new(asset_pair(), Interval::OneMinute);
+        assert!(aggregator
+            .on_trade(&order_filled(10, 0))
+            .unwrap()
+            .is_empty());
+        assert!(aggregator
+            .on_trade(&order_filled(5, 59))
+            .unwrap()
+            .is_empty());
+
+        let closed = aggregator.on_tick(Time::new(60)).unwrap();
+        assert_eq!(closed.len(), 1);
+        let data = closed[0].data.as_ref().unwrap();
+        assert_eq!((data.open.num, data.low.num, data.close.num), (10, 5, 5));
+        assert_eq!(data.volume.num, 2);
+
+        assert!(aggregator.on_trade(&order_filled(1, 30)).is_err());
+        assert!(aggregator.on_tick(Time::new(90)).is_err());
+        assert_eq!(aggregator.open_time(), Some(Time::new(60)));
+
+        assert!(aggregator
+            .on_trade(&order_filled(20, 61))
+            .unwrap()
+            .is_empty());
+        let flushed = aggregator.flush();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].data.as_ref().unwrap().close.num, 20);
+        assert!(aggregator.current().is_none());
+    }
+
+    #[test]
+    fn test_throttled_updates() {
+        let mut aggregator = CandlestickAggregator::new(asset_pair(), Interval::OneHour)
+            .with_update_mode(UpdateMode::Throttle(Duration::from_minutes(1)));
+        let mut updates = vec![];
+        for (price, time) in [(10, 0), (11, 30), (12, 60), (13, 3600), (14, 3610)] {
+            for event in aggregator
+                .on_trade_events(&order_filled(price, time))
+                .unwrap()
+            {
+                match event {
+                    CandlestickEvent::Update(candlestick) => {
+                        updates.push(candlestick.data.unwrap().close.num)
+                    }
+                    CandlestickEvent::Final(candlestick) => {
+                        assert_eq!(candlestick.data.unwrap().close.num, 12)
+                    }
+                }
+            }
+        }
+        assert_eq!(updates, vec![10, 12, 13]);
+    }
+
+    #[test]
+    fn test_gap_fill() {
+        let tests = vec![
+            (
+                GapFill::Empty,
+                vec![(0, Some(10)), (60, None), (120, Some(20)), (180, None)],
+            ),
+            (
+                GapFill::CarryForward,
+                vec![
+                    (0, Some(10)),
+                    (60, Some(10)),
+                    (120, Some(20)),
+                    (180, Some(20)),
+                ],
+            ),
+            (GapFill::Skip, vec![(0, Some(10)), (120, Some(20))]),
+        ];
+        for (gap_fill, expected) in tests {
+            let mut aggregator = CandlestickAggregator::new(asset_pair(), Interval::OneMinute)
+                .with_gap_fill(gap_fill);
+            let mut closed = vec![];
+            closed.extend(aggregator.on_trade(&order_filled(10, 0)).unwrap());
+            closed.extend(aggregator.on_trade(&order_filled(20, 150)).unwrap());
+            closed.extend(aggregator.on_tick(Time::new(180)).unwrap());
+            closed.extend(aggregator.on_tick(Time::new(240)).unwrap());
+            let actual: Vec<(i64, Option<u64>)> = closed
+                .iter()
+                .map(|candlestick| {
+                    let close = candlestick.data.as_ref().map(|data| data.close.num);
+                    (candlestick.open_time.value(), close)
+                })
+                .collect();
+            assert_eq!(actual, expected);
+            if gap_fill == GapFill::CarryForward {
+                let data = closed[1].data.as_ref().unwrap();
+                assert_eq!((data.open.num, data.high.num, data.low.num), (10, 10, 10));
+                assert_eq!(data.volume.num, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_bucket_gap() {
+        let intervals = [
+            Interval::OneSecond,
+            Interval::OneMinute,
+            Interval::ThreeMinutes,
+            Interval::FiveMinutes,
+            Interval::TenMinutes,
+            Interval::FifteenMinutes,
+            Interval::ThirtyMinutes,
+            Interval::OneHour,
+            Interval::TwoHours,
+            Interval::FourHours,
+            Interval::SixHours,
+            Interval::EightHours,
+            Interval::TwelveHours,
+            Interval::OneDay,
+            Interval::OneWeek,
+            Interval::OneMonth,
+            Interval::ThreeMonths,
+            Interval::OneYear,
+            Interval::custom(Duration::from_seconds(90)).unwrap(),
+        ];
+        // 2023-01-01T00:00:00, a Sunday
+        let start = Time::new(1672531200);
+        for interval in intervals {
+            let mut expected = vec![start];
+            for _ in 0..4 {
+                expected.push(interval.next(*expected.last().unwrap()));
+            }
+            let last = expected.pop().unwrap();
+
+            let mut aggregator = CandlestickAggregator::new(asset_pair(), interval);
+            assert!(aggregator
+                .on_trade(&order_filled(10, start.value()))
+                .unwrap()
+                .is_empty());
+            let closed = aggregator
+                .on_trade(&order_filled(20, last.value()))
+                .unwrap();
+            let open_times: Vec<Time> = closed.iter().map(|c| c.open_time).collect();
+            assert_eq!(open_times, expected, "{}", interval);
+            assert!(closed[0].data.is_some());
+            assert!(closed[1..].iter().all(|c| c.data.is_none()));
+
+            let mut aggregator = CandlestickAggregator::new(asset_pair(), interval);
+            assert!(aggregator.on_tick(start).unwrap().is_empty());
+            let closed = aggregator.on_tick(last).unwrap();
+            let open_times: Vec<Time> = closed.iter().map(|c| c.open_time).collect();
+            assert_eq!(open_times, expected, "{}", interval);
+        }
+    }
+}
@@ -0,0 +1,1013 @@
+//! CSV export and import of candlestick series, enabled by the `csv` feature.
+use crate::models::{
+    Alignment, Asset, AssetPair, Candlestick, CandlestickData, DecimalOptions, Duration, Interval,
+    Price, Time, TrailingZeros, Volume,
+};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// CSV column, named after the `Candlestick` and `CandlestickData` fields.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Column {
+    BaseAsset,
+    QuoteAsset,
+    Interval,
+    OpenTime,
+    /// last instant of the bucket, one nanosecond before the next open time.
+    CloseTime,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    QuoteVolume,
+    TradeCount,
+    Vwap,
+    FirstTradeId,
+    LastTradeId,
+    FirstTradeTime,
+    LastTradeTime,
+    TakerBuyVolume,
+    TakerBuyQuoteVolume,
+    TakerSellVolume,
+    TakerSellQuoteVolume,
+}
+
+const COLUMNS: [Column; 21] = [
+    Column::BaseAsset,
+    Column::QuoteAsset,
+    Column::Interval,
+    Column::OpenTime,
+    Column::CloseTime,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+    Column::QuoteVolume,
+    Column::TradeCount,
+    Column::Vwap,
+    Column::FirstTradeId,
+    Column::LastTradeId,
+    Column::FirstTradeTime,
+    Column::LastTradeTime,
+    Column::TakerBuyVolume,
+    Column::TakerBuyQuoteVolume,
+    Column::TakerSellVolume,
+    Column::TakerSellQuoteVolume,
+];
+
+/// columns a candlestick cannot be read without.
+const REQUIRED_COLUMNS: [Column; 6] = [
+    Column::OpenTime,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+];
+
+impl Column {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BaseAsset => "base_asset",
+            Self::QuoteAsset => "quote_asset",
+            Self::Interval => "interval",
+            Self::OpenTime => "open_time",
+            Self::CloseTime => "close_time",
+            Self::Open => "open",
+            Self::High => "high",
+            Self::Low => "low",
+            Self::Close => "close",
+            Self::Volume => "volume",
+            Self::QuoteVolume => "quote_volume",
+            Self::TradeCount => "trade_count",
+            Self::Vwap => "vwap",
+            Self::FirstTradeId => "first_trade_id",
+            Self::LastTradeId => "last_trade_id",
+            Self::FirstTradeTime => "first_trade_time",
+            Self::LastTradeTime => "last_trade_time",
+            Self::TakerBuyVolume => "taker_buy_volume",
+            Self::TakerBuyQuoteVolume => "taker_buy_quote_volume",
+            Self::TakerSellVolume => "taker_sell_volume",
+            Self::TakerSellQuoteVolume => "taker_sell_quote_volume",
+        }
+    }
+
+    /// every column, in declaration order.
+    pub fn all() -> Vec<Column> {
+        COLUMNS.to_vec()
+    }
+
+    /// columns of `CandlestickData`, a row has data when any of them is set.
+    fn is_data(&self) -> bool {
+        !matches!(
+            self,
+            Self::BaseAsset | Self::QuoteAsset | Self::Interval | Self::OpenTime | Self::CloseTime
+        )
+    }
+
+    fn is_price(&self) -> bool {
+        matches!(
+            self,
+            Self::Open | Self::High | Self::Low | Self::Close | Self::Vwap
+        )
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        COLUMNS
+            .iter()
+            .find(|column| column.as_str() == value)
+            .copied()
+            .ok_or_else(|| invalid_csv(format!("unknown column {}", value)))
+    }
+}
+
+/// how times are written and read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeFormat {
+    /// RFC 3339 in UTC, like `Time`'s `Display`.
+    Rfc3339,
+    /// seconds since the unix epoch, rounded toward negative infinity.
+    Seconds,
+    /// milliseconds since the unix epoch, rounded toward negative infinity.
+    Millis,
+    /// nanoseconds since the unix epoch.
+    Nanos,
+}
+
+impl TimeFormat {
+    fn format(&self, time: &Time) -> String {
+        match self {
+            Self::Rfc3339 => time.to_string(),
+            Self::Seconds => time.value().to_string(),
+            Self::Millis => time.as_millis().to_string(),
+            Self::Nanos => time.as_nanos().to_string(),
+        }
+    }
+
+    fn parse(&self, value: &str) -> Result<Time> {
+        if *self == Self::Rfc3339 {
+            return Time::from_str(value);
+        }
+        let invalid = || Error::InvalidTime {
+            value: value.to_owned(),
+        };
+        let timestamp = i64::from_str(value).map_err(|_| invalid())?;
+        match self {
+            Self::Seconds => Time::checked_from_seconds(timestamp),
+            Self::Millis => Time::checked_from_millis(timestamp),
+            _ => Ok(Time::from_nanos(timestamp)),
+        }
+    }
+}
+
+/// layout shared by `CsvWriter` and `CsvReader`.
+#[derive(Debug, Clone)]
+pub struct CsvFormat {
+    /// written in this order, read in this order when there is no header row.
+    pub columns: Vec<Column>,
+    /// first row names the columns, a reader then takes the columns from it.
+    pub has_headers: bool,
+    pub delimiter: u8,
+    pub time_format: TimeFormat,
+    /// scale, rounding and trailing zeros of the open, high, low, close and vwap columns.
+    pub price_decimals: DecimalOptions,
+    /// same for the volume columns.
+    pub volume_decimals: DecimalOptions,
+}
+
+impl Default for CsvFormat {
+    /// OHLCV with RFC 3339 open times and a header row, decimals as they are.
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                Column::OpenTime,
+                Column::Open,
+                Column::High,
+                Column::Low,
+                Column::Close,
+                Column::Volume,
+            ],
+            has_headers: true,
+            delimiter: b',',
+            time_format: TimeFormat::Rfc3339,
+            price_decimals: DecimalOptions::default(),
+            volume_decimals: DecimalOptions::default(),
+        }
+    }
+}
+
+impl CsvFormat {
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_time_format(mut self, time_format: TimeFormat) -> Self {
+        self.time_format = time_format;
+        self
+    }
+
+    pub fn with_price_decimals(mut self, price_decimals: DecimalOptions) -> Self {
+        self.price_decimals = price_decimals;
+        self
+    }
+
+    pub fn with_volume_decimals(mut self, volume_decimals: DecimalOptions) -> Self {
+        self.volume_decimals = volume_decimals;
+        self
+    }
+
+    fn decimals(&self, column: Column) -> &DecimalOptions {
+        if column.is_price() {
+            &self.price_decimals
+        } else {
+            &self.volume_decimals
+        }
+    }
+}
+
+fn invalid_csv(message: impl fmt::Display) -> Error {
+    Error::InvalidCsv {
+        message: message.to_string(),
+    }
+}
+
+/// attaches the line to an error, keeping a line already attached.
+fn line_error(line: Option<u64>, err: Error) -> Error {
+    match (line, err) {
+        (
+            line,
+            Error::Csv {
+                line: None,
+                column,
+                source,
+            },
+        ) => Error::Csv {
+            line,
+            column,
+            source,
+        },
+        (None, err) | (_, err @ Error::Csv { .. }) => err,
+        (line, err) => Error::Csv {
+            line,
+            column: None,
+            source: Box::new(err),
+        },
+    }
+}
+
+fn column_error(column: Column, err: Error) -> Error {
+    Error::Csv {
+        line: None,
+        column: Some(column.to_string()),
+        source: Box::new(err),
+    }
+}
+
+impl From<::csv::Error> for Error {
+    fn from(err: ::csv::Error) -> Self {
+        let line = err.position().map(|position| position.line());
+        let cause = if err.is_io_error() {
+            match err.into_kind() {
+                ::csv::ErrorKind::Io(err) => Error::from(err),
+                _ => unreachable!("checked to be an I/O error"),
+            }
+        } else {
+            invalid_csv(err)
+        };
+        line_error(line, cause)
+    }
+}
+
+/// drops trailing fractional zeros unless the decimal options fix the scale.
+fn trim_decimal(formatted: String, options: &DecimalOptions) -> String {
+    match (options.scale, options.trailing_zeros) {
+        (None, TrailingZeros::Trim) if formatted.contains('.') => formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned(),
+        _ => formatted,
+    }
+}
+
+/// Streams candlesticks to CSV, one row per candlestick.
+/// Data columns of candlesticks without data are left empty.
+pub struct CsvWriter<W: io::Write> {
+    writer: ::csv::Writer<W>,
+    format: CsvFormat,
+    alignment: Alignment,
+    header_written: bool,
+}
+
+impl<W: io::Write> CsvWriter<W> {
+    pub fn new(inner: W, format: CsvFormat) -> Self {
+        let writer = ::csv::WriterBuilder::new()
+            .delimiter(format.delimiter)
+            .has_headers(false)
+            .from_writer(inner);
+        Self {
+            writer,
+            format,
+            alignment: Alignment::default(),
+            header_written: false,
+        }
+    }
+
+    /// alignment of the buckets whose close time is written.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn write<A: Asset>(&mut self, candlestick: &Candlestick<A>) -> Result<()> {
+        self.write_header()?;
+        let record = self
+            .format
+            .columns
+            .iter()
+            .map(|column| self.field(candlestick, *column))
+            .collect::<Result<Vec<String>>>()?;
+        self.writer.write_record(&record)?;
+        Ok(())
+    }
+
+    pub fn write_all<'a, A: Asset + 'a>(
+        &mut self,
+        candlesticks: impl IntoIterator<Item = &'a Candlestick<A>>,
+    ) -> Result<()> {
+        candlesticks
+            .into_iter()
+            .try_for_each(|candlestick| self.write(candlestick))
+    }
+
+    /// writes the header row even if no candlestick was written.
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_header()?;
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        self.writer
+            .into_inner()
+            .map_err(|err| Error::from(err.into_error()))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if self.format.has_headers && !self.header_written {
+            let header = self.format.columns.iter().map(Column::as_str);
+            self.writer.write_record(header)?;
+        }
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn field<A: Asset>(&self, candlestick: &Candlestick<A>, column: Column) -> Result<String> {
+        let time_format = self.format.time_format;
+        let options = self.format.decimals(column);
+        let price = |price: &Price| {
+            let price = match options.scale {
+                Some(scale) => price.rescale(scale, options.rounding)?,
+                None => *price,
+            };
+            Ok::<_, Error>(trim_decimal(price.to_string(), options))
+        };
+        let volume = |volume: &Volume| {
+            let volume = match options.scale {
+                Some(scale) => volume.rescale(scale, options.rounding)?,
+                None => *volume,
+            };
+            Ok::<_, Error>(trim_decimal(volume.to_string(), options))
+        };
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let data = candlestick.data.as_ref();
+        Ok(match column {
+            Column::BaseAsset => candlestick.asset_pair.base_asset.to_string(),
+            Column::QuoteAsset => candlestick.asset_pair.quote_asset.to_string(),
+            Column::Interval => candlestick.interval.to_string(),
+            Column::OpenTime => time_format.format(&candlestick.open_time),
+            Column::CloseTime => {
+                let next = candlestick
+                    .interval
+                    .checked_next_aligned(candlestick.open_time, &self.alignment)?;
+                time_format.format(&next.checked_sub(Duration::from_nanos(1))?)
+            }
+            Column::Open => optional(data.map(|data| price(&data.open)).transpose()?),
+            Column::High => optional(data.map(|data| price(&data.high)).transpose()?),
+            Column::Low => optional(data.map(|data| price(&data.low)).transpose()?),
+            Column::Close => optional(data.map(|data| price(&data.close)).transpose()?),
+            Column::Vwap => optional(data.map(|data| price(&data.vwap)).transpose()?),
+            Column::Volume => optional(data.map(|data| volume(&data.volume)).transpose()?),
+            Column::QuoteVolume => optional(
+                data.and_then(|data| data.quote_volume.as_ref())
+                    .map(volume)
+                    .transpose()?,
+            ),
+            Column::TakerBuyVolume => optional(
+                data.and_then(|data| data.taker_buy_volume.as_ref())
+                    .map(volume)
+                    .transpose()?,
+            ),
+            Column::TakerBuyQuoteVolume => optional(
+                data.and_then(|data| data.taker_buy_quote_volume.as_ref())
+                    .map(volume)
+                    .transpose()?,
+            ),
+            Column::TakerSellVolume => optional(
+                data.and_then(|data| data.taker_sell_volume.as_ref())
+                    .map(volume)
+                    .transpose()?,
+            ),
+            Column::TakerSellQuoteVolume => optional(
+                data.and_then(|data| data.taker_sell_quote_volume.as_ref())
+                    .map(volume)
+                    .transpose()?,
+            ),
+            Column::TradeCount => optional(data.map(|data| data.trade_count.to_string())),
+            Column::FirstTradeId => optional(
+                data.and_then(|data| data.first_trade_id)
+                    .map(|id| id.to_string()),
+            ),
+            Column::LastTradeId => optional(
+                data.and_then(|data| data.last_trade_id)
+                    .map(|id| id.to_string()),
+            ),
+            Column::FirstTradeTime => optional(
+                data.and_then(|data| data.first_trade_time)
+                    .map(|time| time_format.format(&time)),
+            ),
+            Column::LastTradeTime => optional(
+                data.and_then(|data| data.last_trade_time)
+                    .map(|time| time_format.format(&time)),
+            ),
+        })
+    }
+}
+
+/// Reads candlesticks of a single asset pair and interval back from CSV.
+///
+/// rows must be in open time order, open at bucket starts and, when present, name the same
+/// asset pair and interval. Missing optional columns take zero volumes and trade count, and the
+/// close price as vwap. A row whose data columns are all empty is a candlestick without data.
+/// Errors carry the line of the offending row.
+pub struct CsvReader<R: io::Read, A: Asset> {
+    records: ::csv::StringRecordsIntoIter<R>,
+    columns: HashMap<Column, usize>,
+    asset_pair: AssetPair<A>,
+    interval: Interval,
+    alignment: Alignment,
+    format: CsvFormat,
+    last_open_time: Option<Time>,
+}
+
+impl<R: io::Read, A: Asset> CsvReader<R, A> {
+    /// reads the header row, if the format has one.
+    pub fn new(
+        inner: R,
+        asset_pair: AssetPair<A>,
+        interval: Interval,
+        format: CsvFormat,
+    ) -> Result<Self> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(format.delimiter)
+            .has_headers(format.has_headers)
+            .from_reader(inner);
+        let (columns, line) = if format.has_headers {
+            let columns = reader
+                .headers()?
+                .iter()
+                .map(|name| Column::from_str(name).map_err(|err| line_error(Some(1), err)))
+                .collect::<Result<Vec<Column>>>()?;
+            (columns, Some(1))
+        } else {
+            (format.columns.clone(), None)
+        };
+        let mut indexes = HashMap::new();
+        for (index, column) in columns.into_iter().enumerate() {
+            if indexes.insert(column, index).is_some() {
+                return Err(line_error(
+                    line,
+                    invalid_csv(format!("duplicate column {}", column)),
+                ));
+            }
+        }
+        if let Some(column) = REQUIRED_COLUMNS
+            .iter()
+            .find(|column| !indexes.contains_key(column))
+        {
+            return Err(line_error(
+                line,
+                invalid_csv(format!("missing column {}", column)),
+            ));
+        }
+        Ok(Self {
+            records: reader.into_records(),
+            columns: indexes,
+            asset_pair,
+            interval,
+            alignment: Alignment::default(),
+            format,
+            last_open_time: None,
+        })
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    fn read(&mut self, record: &::csv::StringRecord) -> Result<Candlestick<A>> {
+        let field = |column: Column| {
+            self.columns
+                .get(&column)
+                .and_then(|index| record.get(*index))
+                .filter(|value| !value.is_empty())
+        };
+        let required = |column: Column| {
+            field(column).ok_or_else(|| invalid_csv(format!("empty column {}", column)))
+        };
+        let in_column = |column: Column| move |err: Error| column_error(column, err);
+        let time_format = self.format.time_format;
+        let time = |column: Column| {
+            field(column)
+                .map(|value| time_format.parse(value).map_err(in_column(column)))
+                .transpose()
+        };
+        let price = |column: Column| {
+            Price::parse_with(required(column)?, &self.format.price_decimals)
+                .map_err(in_column(column))
+        };
+        let volume = |column: Column| {
+            field(column)
+                .map(|value| {
+                    Volume::parse_with(value, &self.format.volume_decimals)
+                        .map_err(in_column(column))
+                })
+                .transpose()
+        };
+        let integer = |column: Column| {
+            field(column)
+                .map(|value| {
+                    u64::from_str(value).map_err(|err| column_error(column, invalid_csv(err)))
+                })
+                .transpose()
+        };
+
+        for (column, expected) in [
+            (Column::BaseAsset, &self.asset_pair.base_asset),
+            (Column::QuoteAsset, &self.asset_pair.quote_asset),
+        ] {
+            if let Some(actual) = field(column) {
+                if actual != expected.to_string() {
+                    return Err(Error::asset_pair_mismatch(expected, actual));
+                }
+            }
+        }
+        if let Some(actual) = field(Column::Interval) {
+            let actual = Interval::from_str(actual).map_err(in_column(Column::Interval))?;
+            if actual != self.interval {
+                return Err(Error::IntervalMismatch {
+                    expected: self.interval,
+                    actual,
+                });
+            }
+        }
+
+        let open_time = time_format
+            .parse(required(Column::OpenTime)?)
+            .map_err(in_column(Column::OpenTime))?;
+        if !self
+            .interval
+            .is_valid_time_aligned(&open_time, &self.alignment)
+        {
+            return Err(Error::MisalignedTime {
+                interval: self.interval,
+                time: open_time,
+            });
+        }
+        if let Some(current) = self.last_open_time.filter(|current| *current >= open_time) {
+            return Err(Error::RegressingTime {
+                current,
+                time: open_time,
+            });
+        }
+        if let Some(close_time) = time(Column::CloseTime)? {
+            let next = self
+                .interval
+                .checked_next_aligned(open_time, &self.alignment)?;
+            if close_time < open_time || close_time >= next {
+                return Err(invalid_csv(format!(
+                    "close time {} outside of the bucket",
+                    close_time
+                )));
+            }
+        }
+
+        let has_data = self
+            .columns
+            .keys()
+            .any(|column| column.is_data() && field(*column).is_some());
+        let data = if has_data {
+            let (open, high, low, close) = (
+                price(Column::Open)?,
+                price(Column::High)?,
+                price(Column::Low)?,
+                price(Column::Close)?,
+            );
+            if high < open.max(close) || low > open.min(close) {
+                return Err(invalid_csv("high and low do not enclose open and close"));
+            }
+            Some(CandlestickData {
+                open,
+                high,
+                low,
+                close,
+                volume: volume(Column::Volume)?
+                    .ok_or_else(|| invalid_csv(format!("empty column {}", Column::Volume)))?,
+                quote_volume: volume(Column::QuoteVolume)?,
+                trade_count: integer(Column::TradeCount)?.unwrap_or(0),
+                vwap: match field(Column::Vwap) {
+                    Some(_) => price(Column::Vwap)?,
+                    None => close,
+                },
+                first_trade_id: integer(Column::FirstTradeId)?,
+                last_trade_id: integer(Column::LastTradeId)?,
+                first_trade_time: time(Column::FirstTradeTime)?,
+                last_trade_time: time(Column::LastTradeTime)?,
+                taker_buy_volume: volume(Column::TakerBuyVolume)?,
+                taker_buy_quote_volume: volume(Column::TakerBuyQuoteVolume)?,
+                taker_sell_volume: volume(Column::TakerSellVolume)?,
+                taker_sell_quote_volume: volume(Column::TakerSellQuoteVolume)?,
+            })
+        } else {
+            None
+        };
+
+        self.last_open_time = Some(open_time);
+        Ok(Candlestick::new_with_data(
+            self.asset_pair.clone(),
+            open_time,
+            self.interval,
+            data,
+        ))
+    }
+}
+
+impl<R: io::Read, A: Asset> Iterator for CsvReader<R, A> {
+    type Item = Result<Candlestick<A>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err.into())),
+        };
+        let line = record.position().map(|position| position.line());
+        Some(self.read(&record).map_err(|err| line_error(line, err)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{Quantity, RoundingMode};
+    use crate::test_fixtures::asset_pair;
+
+    fn candlesticks() -> Vec<Candlestick<String>> {
+        let mut data =
+            CandlestickData::new_with_price_and_quantity(Price::new(10050, 2), Quantity::new(5, 1))
+                .unwrap();
+        data.add_price_and_quantity(Price::new(9900, 2), Quantity::new(1500, 3))
+            .unwrap();
+        vec![
+            Candlestick::new_with_data(
+                asset_pair(),
+                Time::from_str("2023-01-01T00:00:00Z").unwrap(),
+                Interval::OneMinute,
+                Some(data),
+            ),
+            Candlestick::new(
+                asset_pair(),
+                Time::from_str("2023-01-01T00:01:00Z").unwrap(),
+                Interval::OneMinute,
+            ),
+        ]
+    }
+
+    fn write(candlesticks: &[Candlestick<String>], format: CsvFormat) -> String {
+        let mut writer = CsvWriter::new(vec![], format);
+        writer.write_all(candlesticks).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    fn read(csv: &str, format: CsvFormat) -> Result<Vec<Candlestick<String>>> {
+        CsvReader::new(csv.as_bytes(), asset_pair(), Interval::OneMinute, format)?.collect()
+    }
+
+    #[test]
+    fn test_write() {
+        let csv = write(&candlesticks(), CsvFormat::default());
+        assert_eq!(
+            csv,
+            "open_time,open,high,low,close,volume\n\
+             2023-01-01T00:00:00Z,100.50,100.50,99.00,99.00,2.000\n\
+             2023-01-01T00:01:00Z,,,,,\n"
+        );
+
+        let format = CsvFormat::default()
+            .with_columns(vec![
+                Column::Interval,
+                Column::OpenTime,
+                Column::CloseTime,
+                Column::Close,
+                Column::Vwap,
+                Column::QuoteVolume,
+                Column::TradeCount,
+            ])
+            .with_headers(false)
+            .with_delimiter(b';')
+            .with_time_format(TimeFormat::Millis)
+            .with_price_decimals(DecimalOptions::default().with_trailing_zeros(TrailingZeros::Trim))
+            .with_volume_decimals(
+                DecimalOptions::default()
+                    .with_scale(2)
+                    .with_rounding(RoundingMode::HalfUp),
+            );
+        let csv = write(&candlesticks(), format);
+        assert_eq!(
+            csv,
+            "1m;1672531200000;1672531259999;99;99.38;198.75;2\n\
+             1m;1672531260000;1672531319999;;;;\n"
+        );
+        // the bucket after the last day is out of range.
+        let last_day = Interval::OneDay.floor(&Time::from_nanos(i64::MAX));
+        let candlestick = Candlestick::new(asset_pair(), last_day, Interval::OneDay);
+        let mut writer = CsvWriter::new(vec![], CsvFormat::default().with_columns(Column::all()));
+        assert!(matches!(
+            writer.write(&candlestick),
+            Err(Error::TimeOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_io_error() {
+        struct BrokenPipe;
+
+        impl io::Write for BrokenPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = CsvWriter::new(BrokenPipe, CsvFormat::default());
+        writer.write_all(&candlesticks()).unwrap();
+        let err = writer.flush().unwrap_err();
+        assert!(matches!(&err, Error::Io { source } if source.kind() == io::ErrorKind::BrokenPipe));
+        let source = std::error::Error::source(&err).unwrap();
+        let source = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let format = CsvFormat::default()
+            .with_columns(Column::all())
+            .with_time_format(TimeFormat::Nanos);
+        let csv = write(&candlesticks(), format.clone());
+        let actual = read(&csv, format.clone()).unwrap();
+        assert_eq!(write(&actual, format), csv);
+        assert_eq!(actual.len(), 2);
+        assert!(actual[1].data.is_none());
+        let data = actual[0].data.as_ref().unwrap();
+        assert_eq!((data.low.num, data.low.scale), (9900, 2));
+        assert_eq!(data.trade_count, 2);
+    }
+
+    #[test]
+    fn test_read() {
+        // columns come from the header, in any order.
+        let csv = "volume,close,low,high,open,open_time\n\
+                   1.5,11,9,12,10,2023-01-01T00:00:00Z\n";
+        let candlesticks = read(csv, CsvFormat::default()).unwrap();
+        let data = candlesticks[0].data.as_ref().unwrap();
+        assert_eq!((data.open.num, data.high.num, data.vwap.num), (10, 12, 11));
+        assert!(data.quote_volume.is_none() && data.taker_buy_volume.is_none());
+        assert_eq!(data.trade_count, 0);
+
+        let format = CsvFormat::default()
+            .with_headers(false)
+            .with_time_format(TimeFormat::Seconds)
+            .with_price_decimals(DecimalOptions::default().with_scale(1));
+        let candlesticks = read("1672531200,10.04,12,9,11.05,1\n", format).unwrap();
+        let data = candlesticks[0].data.as_ref().unwrap();
+        assert_eq!(
+            (data.open.num, data.close.num, data.close.scale),
+            (100, 110, 1)
+        );
+    }
+
+    #[test]
+    fn test_read_and_resample() {
+        // without quote volumes, the VWAPs are weighted by the volumes.
+        let csv = "open_time,open,high,low,close,volume\n\
+                   2023-01-01T00:00:00Z,100,100,100,100,1\n\
+                   2023-01-01T00:01:00Z,100,100,100,100,3\n\
+                   2023-01-01T00:05:00Z,100,100,100,100,1\n\
+                   2023-01-01T00:06:00Z,200,200,200,200,3\n";
+        let candlesticks = read(csv, CsvFormat::default()).unwrap();
+        let resampled =
+            crate::resample(candlesticks, Interval::FiveMinutes, Alignment::default()).unwrap();
+        let data: Vec<_> = resampled
+            .iter()
+            .map(|resampled| resampled.candlestick.data.as_ref().unwrap())
+            .collect();
+        assert_eq!(data.len(), 2);
+        assert_eq!((data[0].vwap.num, data[0].volume.num), (100, 4));
+        // (100 * 1 + 200 * 3) / 4
+        assert_eq!((data[1].vwap.num, data[1].volume.num), (175, 4));
+        assert!(data.iter().all(|data| data.quote_volume.is_none()));
+
+        // quote volumes are summed when every row has one.
+        let format = CsvFormat::default().with_columns(vec![
+            Column::OpenTime,
+            Column::Open,
+            Column::High,
+            Column::Low,
+            Column::Close,
+            Column::Volume,
+            Column::QuoteVolume,
+        ]);
+        let csv = "open_time,open,high,low,close,volume,quote_volume\n\
+                   2023-01-01T00:00:00Z,100,110,90,100,1,90\n\
+                   2023-01-01T00:01:00Z,100,110,90,100,3,330\n";
+        let candlesticks = read(csv, format).unwrap();
+        let resampled =
+            crate::resample(candlesticks, Interval::FiveMinutes, Alignment::default()).unwrap();
+        let data = resampled[0].candlestick.data.as_ref().unwrap();
+        assert_eq!(data.quote_volume.map(|volume| volume.num), Some(420));
+        assert_eq!(data.vwap.num, 105);
+    }
+
+    #[test]
+    fn test_invalid_read() {
+        let header = "open_time,open,high,low,close,volume\n";
+        let tests = vec![
+            (
+                "open_time,open,high,low,close\n",
+                1,
+                "missing column volume",
+            ),
+            (
+                "open_time,open,high,low,close,volume,bid\n",
+                1,
+                "unknown column bid",
+            ),
+            ("2023-01-01T00:00:30Z,1,1,1,1,1\n", 2, "not aligned"),
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1,-1\n",
+                2,
+                "in column volume: Negative",
+            ),
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1,1\n2023-01-01T00:00:00Z,1,1,1,1,1\n",
+                3,
+                "not after",
+            ),
+            ("2023-01-01T00:00:00Z,1,1,1,1,\n", 2, "empty column volume"),
+            ("2023-01-01T00:00:00Z,2,1,1,1,1\n", 2, "enclose"),
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1,1\n2023-01-01T00:01:00Z,x,1,1,1,1\n",
+                3,
+                "in column open: Illegal",
+            ),
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1\n",
+                2,
+                "found record with 5 fields",
+            ),
+        ];
+        for (body, expected_line, expected_message) in tests {
+            let csv = if body.starts_with("open_time") {
+                body.to_owned()
+            } else {
+                format!("{}{}", header, body)
+            };
+            let err = read(&csv, CsvFormat::default()).unwrap_err();
+            match &err {
+                Error::Csv {
+                    line: Some(line), ..
+                } => {
+                    assert_eq!(*line, expected_line, "{}", err);
+                    assert!(err.to_string().contains(expected_message), "{}", err);
+                }
+                err => panic!("unexpected error {:?}", err),
+            }
+        }
+
+        let csv = "interval,open_time,open,high,low,close,volume\n\
+                   5m,2023-01-01T00:00:00Z,1,1,1,1,1\n";
+        let err = read(csv, CsvFormat::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "CSV error at line 2: Interval mismatch: expected 1m, got 5m"
+        );
+    }
+
+    #[test]
+    fn test_typed_error_causes() {
+        use std::mem::discriminant;
+
+        let header = "open_time,open,high,low,close,volume\n";
+        let value = String::new;
+        let tests = vec![
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1,1.5x\n",
+                "volume",
+                Error::InvalidDecimal { value: value() },
+            ),
+            (
+                "2023-01-01T00:00:00Z,1,1,1,1,-1\n",
+                "volume",
+                Error::NegativeDecimal { value: value() },
+            ),
+            (
+                "2023-01-01T00:00:00Z,x,1,1,1,1\n",
+                "open",
+                Error::InvalidDecimal { value: value() },
+            ),
+            (
+                "2023-01-01,1,1,1,1,1\n",
+                "open_time",
+                Error::InvalidTime { value: value() },
+            ),
+        ];
+        for (row, expected_column, expected_cause) in tests {
+            let err = read(&format!("{}{}", header, row), CsvFormat::default()).unwrap_err();
+            match &err {
+                Error::Csv {
+                    line: Some(2),
+                    column: Some(column),
+                    source,
+                } => {
+                    assert_eq!(column, expected_column, "{}", err);
+                    assert_eq!(discriminant(&**source), discriminant(&expected_cause));
+                }
+                err => panic!("unexpected error {:?}", err),
+            }
+            let source = std::error::Error::source(&err).unwrap();
+            let source = source.downcast_ref::<Error>().unwrap();
+            assert_eq!(discriminant(source), discriminant(&expected_cause));
+        }
+    }
+
+    #[test]
+    fn test_rows_with_optional_data() {
+        // a row with only optional data columns is not read as a bucket without data.
+        for column in [
+            Column::Vwap,
+            Column::QuoteVolume,
+            Column::TradeCount,
+            Column::FirstTradeId,
+            Column::TakerSellVolume,
+        ] {
+            let csv = format!(
+                "open_time,open,high,low,close,volume,{}\n\
+                 2023-01-01T00:00:00Z,,,,,,3\n",
+                column
+            );
+            let err = read(&csv, CsvFormat::default()).unwrap_err();
+            assert!(err.to_string().contains("empty column open"), "{}", err);
+        }
+        let csv = "open_time,close_time,open,high,low,close,volume,trade_count\n\
+                   2023-01-01T00:00:00Z,2023-01-01T00:00:59Z,,,,,,\n";
+        assert!(read(csv, CsvFormat::default()).unwrap()[0].data.is_none());
+    }
+}
@@ -1,9 +1,88 @@
-use std::fmt;
+use crate::models::{Interval, Time};
+use std::sync::Arc;
+use std::{fmt, io};
 
 #[derive(Debug, Clone)]
 pub enum Error {
     InvalidInterval {
         value: String,
+    },
+    InvalidTime {
+        value: String,
+    },
+    InvalidDecimal {
+        value: String,
+    },
+    /// fixed point numbers are unsigned.
+    NegativeDecimal {
+        value: String,
+    },
+    /// date time outside of the nanosecond range of `Time`.
+    TimeOutOfRange {
+        value: String,
+    },
+    /// open time is not the start of an interval bucket.
+    MisalignedTime {
+        interval: Interval,
+        time: Time,
+    },
+    /// open time is not after the candlestick currently being built.
+    RegressingTime {
+        current: Time,
+        time: Time,
+    },
+    /// candlestick of another interval than expected.
+    IntervalMismatch {
+        expected: Interval,
+        actual: Interval,
+    },
+    /// candlesticks of different buckets.
+    OpenTimeMismatch {
+        expected: Time,
+        actual: Time,
+    },
+    /// buckets of the source interval do not tile the buckets of the target interval.
+    IncompatibleIntervals {
+        source: Interval,
+        target: Interval,
+    },
+    /// filled order belongs to another asset pair.
+    AssetPairMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// output receiver has been dropped.
+    OutputDisconnected,
+    /// malformed CSV, e.g. a missing column.
+    InvalidCsv {
+        message: String,
+    },
+    /// reading or writing failed, shared so that the error stays cloneable.
+    Io {
+        source: Arc<io::Error>,
+    },
+    /// error in a CSV file, at the line and column of the offending field when known.
+    Csv {
+        line: Option<u64>,
+        column: Option<String>,
+        source: Box<Error>,
+    },
+    /// fixed point result does not fit.
+    Overflow,
+    /// fixed point result is negative.
+    Underflow,
+    DivisionByZero,
+}
+
+impl Error {
+    pub(crate) fn asset_pair_mismatch(
+        expected: impl fmt::Display,
+        actual: impl fmt::Display,
+    ) -> Self {
+        Self::AssetPairMismatch {
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
     }
 }
 
@@ -13,6 +92,102 @@ impl fmt::Display for Error {
             Self::InvalidInterval { value } => {
                 write!(f, "Illegal interval string: {}", value)
             }
+            Self::InvalidTime { value } => {
+                write!(f, "Illegal time string: {}", value)
+            }
+            Self::InvalidDecimal { value } => {
+                write!(f, "Illegal decimal string: {}", value)
+            }
+            Self::NegativeDecimal { value } => {
+                write!(f, "Negative decimal string: {}", value)
+            }
+            Self::TimeOutOfRange { value } => {
+                write!(f, "Time {} is out of range", value)
+            }
+            Self::MisalignedTime { interval, time } => {
+                write!(f, "Time {} is not aligned to interval {}", time, interval)
+            }
+            Self::RegressingTime { current, time } => {
+                write!(
+                    f,
+                    "Time {} is not after current open time {}",
+                    time, current
+                )
+            }
+            Self::IntervalMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Interval mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::OpenTimeMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Open time mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::IncompatibleIntervals { source, target } => {
+                write!(f, "Interval {} cannot be resampled into {}", source, target)
+            }
+            Self::AssetPairMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Asset pair mismatch: expected {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::OutputDisconnected => {
+                write!(f, "Output channel is disconnected")
+            }
+            Self::InvalidCsv { message } => {
+                write!(f, "Invalid CSV: {}", message)
+            }
+            Self::Io { source } => {
+                write!(f, "I/O error: {}", source)
+            }
+            Self::Csv {
+                line,
+                column,
+                source,
+            } => {
+                write!(f, "CSV error")?;
+                if let Some(line) = line {
+                    write!(f, " at line {}", line)?;
+                }
+                if let Some(column) = column {
+                    write!(f, " in column {}", column)?;
+                }
+                write!(f, ": {}", source)
+            }
+            Self::Overflow => {
+                write!(f, "Arithmetic overflow")
+            }
+            Self::Underflow => {
+                write!(f, "Arithmetic underflow")
+            }
+            Self::DivisionByZero => {
+                write!(f, "Division by zero")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io {
+            source: Arc::new(err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Csv { source, .. } => Some(source.as_ref()),
+            Self::Io { source } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
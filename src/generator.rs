@@ -1,19 +1,115 @@
 mod input;
-pub use input::CandlestickGeneratorInput;
+pub use input::{CandlestickGeneratorInput, MultiPairCandlestickGeneratorInput};
 
 mod output;
-pub use output::CandlestickGeneratorOutput;
+pub use output::{
+    CandlestickGeneratorEvents, CandlestickGeneratorOutput,
+    MultiIntervalCandlestickGeneratorEvents, MultiIntervalCandlestickGeneratorOutput,
+    MultiPairCandlestickGeneratorEvents, MultiPairCandlestickGeneratorOutput,
+};
 
-use crate::CandlestickData;
+mod policy;
+use policy::ViolationHandler;
+pub use policy::{ViolationAction, ViolationPolicy};
+
+mod multi_interval;
+pub use multi_interval::MultiIntervalCandlestickGenerator;
+
+mod multi_pair;
+pub use multi_pair::MultiPairCandlestickGenerator;
+
+#[cfg(feature = "tokio")]
+mod async_generator;
+#[cfg(feature = "tokio")]
+pub use async_generator::AsyncCandlestickGenerator;
+
+use crate::{CandlestickAggregator, CandlestickEvent, Error, GapFill, Result, UpdateMode};
+use std::future::Future;
+use std::pin::pin;
 use std::sync::mpsc;
+use std::task::{Context, Poll, Waker};
+
+use crate::models::{Alignment, Asset, AssetPair, Candlestick, Interval};
+
+/// channel a generator sends its output to.
+pub(crate) trait OutputChannel<T> {
+    async fn send_output(&self, value: T) -> Result<()>;
+}
+
+impl<T> OutputChannel<T> for mpsc::Sender<T> {
+    async fn send_output(&self, value: T) -> Result<()> {
+        self.send(value).map_err(|_| Error::OutputDisconnected)
+    }
+}
 
-use crate::models::{Asset, AssetPair, Candlestick, Interval};
+#[cfg(feature = "tokio")]
+impl<T> OutputChannel<T> for tokio::sync::mpsc::Sender<T> {
+    async fn send_output(&self, value: T) -> Result<()> {
+        self.send(value)
+            .await
+            .map_err(|_| Error::OutputDisconnected)
+    }
+}
+
+/// closed candlesticks only, or every event, depending on how the generator was built.
+enum OutputSender<C, E> {
+    Closed(C),
+    Events(E),
+}
+
+impl<C, E> OutputSender<C, E> {
+    /// `closed_output` and `events_output` wrap what is sent, nothing is sent for no candlestick.
+    async fn send<A: Asset, O, U>(
+        &self,
+        events: Vec<CandlestickEvent<A>>,
+        closed_output: impl FnOnce(Vec<Candlestick<A>>) -> O,
+        events_output: impl FnOnce(Vec<CandlestickEvent<A>>) -> U,
+    ) -> Result<()>
+    where
+        C: OutputChannel<O>,
+        E: OutputChannel<U>,
+    {
+        match self {
+            Self::Closed(output_sender) => {
+                let results = closed(events);
+                if !results.is_empty() {
+                    output_sender.send_output(closed_output(results)).await?;
+                }
+            }
+            Self::Events(event_sender) => {
+                if !events.is_empty() {
+                    event_sender.send_output(events_output(events)).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<O, U> OutputSender<mpsc::Sender<O>, mpsc::Sender<U>> {
+    /// `send` for the std channels, which never wait.
+    fn send_blocking<A: Asset>(
+        &self,
+        events: Vec<CandlestickEvent<A>>,
+        closed_output: impl FnOnce(Vec<Candlestick<A>>) -> O,
+        events_output: impl FnOnce(Vec<CandlestickEvent<A>>) -> U,
+    ) -> Result<()> {
+        let send = pin!(self.send(events, closed_output, events_output));
+        match send.poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => result,
+            Poll::Pending => unreachable!("std channels send without waiting"),
+        }
+    }
+}
 
 pub struct CandlestickGenerator<A: Asset> {
-    asset_pair: AssetPair<A>,
-    interval: Interval,
+    aggregator: CandlestickAggregator<A>,
     input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
-    output_sender: mpsc::Sender<CandlestickGeneratorOutput<A>>,
+    output_sender: OutputSender<
+        mpsc::Sender<CandlestickGeneratorOutput<A>>,
+        mpsc::Sender<CandlestickGeneratorEvents<A>>,
+    >,
+    violation_handler: ViolationHandler,
 }
 
 impl<A: Asset> CandlestickGenerator<A> {
@@ -24,83 +120,249 @@ impl<A: Asset> CandlestickGenerator<A> {
         output_sender: mpsc::Sender<CandlestickGeneratorOutput<A>>,
     ) -> Self {
         Self {
-            asset_pair,
-            interval,
+            aggregator: CandlestickAggregator::new(asset_pair, interval),
+            input_receiver,
+            output_sender: OutputSender::Closed(output_sender),
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    /// Generator reporting candlesticks in progress as well as closed ones, according to the update mode.
+    pub fn new_with_events(
+        asset_pair: AssetPair<A>,
+        interval: Interval,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        event_sender: mpsc::Sender<CandlestickGeneratorEvents<A>>,
+    ) -> Self {
+        Self {
+            aggregator: CandlestickAggregator::new(asset_pair, interval)
+                .with_update_mode(update_mode),
+            input_receiver,
+            output_sender: OutputSender::Events(event_sender),
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.aggregator = self.aggregator.with_gap_fill(gap_fill);
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.aggregator = self.aggregator.with_alignment(alignment);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.violation_handler = self.violation_handler.with_policy(policy);
+        self
+    }
+
+    /// skipped violations are reported to this sender.
+    pub fn with_error_sender(mut self, error_sender: mpsc::Sender<Error>) -> Self {
+        self.violation_handler = self.violation_handler.with_reporter(error_sender);
+        self
+    }
+
+    pub fn start(mut self) -> Result<()> {
+        let aggregator = &mut self.aggregator;
+        let output_sender = &self.output_sender;
+        self.violation_handler.run(
+            self.input_receiver.iter(),
+            CandlestickGeneratorInput::is_terminate,
+            |input| output_sender.send_blocking(apply_input(aggregator, input)?, |c| c, |e| e),
+        )
+    }
+}
+
+fn apply_input<A: Asset>(
+    aggregator: &mut CandlestickAggregator<A>,
+    input: CandlestickGeneratorInput<A>,
+) -> Result<CandlestickGeneratorEvents<A>> {
+    let closed = match input {
+        CandlestickGeneratorInput::Tick { open_time } => aggregator.on_tick(open_time)?,
+        CandlestickGeneratorInput::OrderFilled { order_filled } => {
+            return aggregator.on_trade_events(&order_filled);
+        }
+        CandlestickGeneratorInput::Terminate { flush: true } => aggregator.flush(),
+        CandlestickGeneratorInput::Terminate { flush: false } => vec![],
+    };
+    Ok(finals(closed))
+}
+
+fn finals<A: Asset>(closed: Vec<Candlestick<A>>) -> Vec<CandlestickEvent<A>> {
+    closed.into_iter().map(CandlestickEvent::Final).collect()
+}
+
+fn closed<A: Asset>(events: Vec<CandlestickEvent<A>>) -> Vec<Candlestick<A>> {
+    events
+        .into_iter()
+        .filter_map(|event| match event {
+            CandlestickEvent::Final(candlestick) => Some(candlestick),
+            CandlestickEvent::Update(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Time;
+    use crate::test_fixtures::{asset_pair, order_filled, order_filled_for};
+
+    #[test]
+    fn test_stop_on_violation() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, _output_receiver) = mpsc::channel();
+        let generator = CandlestickGenerator::new(
+            asset_pair(),
+            Interval::OneMinute,
             input_receiver,
             output_sender,
+        );
+        input_sender
+            .send(CandlestickGeneratorInput::Tick {
+                open_time: Time::new(30),
+            })
+            .unwrap();
+        assert!(matches!(
+            generator.start(),
+            Err(Error::MisalignedTime { .. })
+        ));
+    }
+
+    #[test]
+    fn test_skip_and_report_violation() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let (error_sender, error_receiver) = mpsc::channel();
+        let generator = CandlestickGenerator::new(
+            asset_pair(),
+            Interval::OneMinute,
+            input_receiver,
+            output_sender,
+        )
+        .with_policy(ViolationPolicy::new(ViolationAction::Skip))
+        .with_error_sender(error_sender);
+        let inputs = vec![
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(60),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("ETH", 10, 70),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(0),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 75),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(30, 30),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(120),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ];
+        for input in inputs {
+            input_sender.send(input).unwrap();
         }
+        generator.start().unwrap();
+
+        let errors: Vec<Error> = error_receiver.try_iter().collect();
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(errors[0], Error::AssetPairMismatch { .. }));
+        assert!(matches!(errors[1], Error::RegressingTime { .. }));
+        assert!(matches!(errors[2], Error::RegressingTime { .. }));
+
+        let outputs: Vec<CandlestickGeneratorOutput<String>> = output_receiver.try_iter().collect();
+        assert_eq!(outputs.len(), 1);
+        let data = outputs[0][0].data.as_ref().unwrap();
+        assert_eq!(data.close.num, 20);
     }
 
-    pub fn start(self) {
-        let mut output: Option<Candlestick<A>> = None;
-        for input in self.input_receiver {
-            match input {
-                CandlestickGeneratorInput::Tick { open_time } => {
-                    assert!(self.interval.is_valid_time(&open_time));
-                    if let Some(output) = &output {
-                        assert!(output.open_time < open_time);
-                        self.output_sender
-                            .send(vec![output.clone()])
-                            .expect("output must be sent.");
-                    }
-                    output = Some(Candlestick::new(
-                        self.asset_pair.clone(),
-                        open_time,
-                        self.interval,
-                    ));
-                }
-                CandlestickGeneratorInput::OrderFilled {
-                    open_time,
-                    order_filled,
-                } => {
-                    assert!(self.interval.is_valid_time(&open_time));
-                    assert_eq!(order_filled.asset_pair, self.asset_pair);
-                    if let Some(current) = &mut output {
-                        assert!(current.open_time <= open_time);
-                        if current.open_time < open_time {
-                            // close the output and reset output
-                            let mut results = vec![current.clone()];
-                            loop {
-                                let next_time = self.interval.next(current.open_time);
-                                if next_time >= open_time {
-                                    break;
-                                }
-                                results.push(Candlestick::new(
-                                    self.asset_pair.clone(),
-                                    next_time,
-                                    self.interval,
-                                ));
-                            }
-                            self.output_sender
-                                .send(results)
-                                .expect("output must be sent.");
-                            current.reset(
-                                open_time,
-                                Some(CandlestickData::new_with_price_and_quantity(
-                                    order_filled.price,
-                                    order_filled.quantity,
-                                )),
-                            );
-                        } else {
-                            // update exact the same open_time data
-                            current.add_order_filled(order_filled);
-                        }
-                    } else {
-                        output = Some(Candlestick::new_with_data(
-                            self.asset_pair.clone(),
-                            open_time,
-                            self.interval,
-                            Some(CandlestickData::new_with_price_and_quantity(
-                                order_filled.price,
-                                order_filled.quantity,
-                            )),
-                        ));
-                    }
-                }
-                CandlestickGeneratorInput::Terminate => {
-                    break;
-                }
-            }
+    #[test]
+    fn test_output_disconnected() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        drop(output_receiver);
+        let generator = CandlestickGenerator::new(
+            asset_pair(),
+            Interval::OneMinute,
+            input_receiver,
+            output_sender,
+        );
+        for open_time in [0, 60] {
+            input_sender
+                .send(CandlestickGeneratorInput::Tick {
+                    open_time: Time::new(open_time),
+                })
+                .unwrap();
         }
+        assert!(matches!(generator.start(), Err(Error::OutputDisconnected)));
+    }
+
+    fn generate(inputs: Vec<CandlestickGeneratorInput<String>>) -> Vec<(i64, Option<u64>)> {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let generator = CandlestickGenerator::new(
+            asset_pair(),
+            Interval::OneMinute,
+            input_receiver,
+            output_sender,
+        );
+        for input in inputs {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+        output_receiver
+            .try_iter()
+            .flatten()
+            .map(|candlestick| {
+                let close = candlestick.data.as_ref().map(|data| data.close.num);
+                (candlestick.open_time.value(), close)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_live_and_archive_series() {
+        let live = generate(vec![
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(0),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 10),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(180),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 190),
+            },
+            CandlestickGeneratorInput::Terminate { flush: true },
+        ]);
+        let archive = generate(vec![
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 10),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 190),
+            },
+            CandlestickGeneratorInput::Terminate { flush: true },
+        ]);
+        let expected = vec![(0, Some(10)), (60, None), (120, None), (180, Some(20))];
+        assert_eq!(live, expected);
+        assert_eq!(archive, expected);
+
+        let unflushed = generate(vec![
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 10),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ]);
+        assert!(unflushed.is_empty());
     }
 }
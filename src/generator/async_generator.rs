@@ -0,0 +1,176 @@
+use super::{
+    apply_input, CandlestickAggregator, CandlestickGeneratorEvents, CandlestickGeneratorInput,
+    CandlestickGeneratorOutput, OutputSender, ViolationHandler, ViolationPolicy,
+};
+use crate::models::{Alignment, Asset, AssetPair, Interval};
+use crate::{Error, GapFill, Result, UpdateMode};
+use tokio::sync::mpsc;
+
+/// `CandlestickGenerator` driven by tokio channels instead of blocking a thread.
+pub struct AsyncCandlestickGenerator<A: Asset> {
+    aggregator: CandlestickAggregator<A>,
+    input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+    output_sender: OutputSender<
+        mpsc::Sender<CandlestickGeneratorOutput<A>>,
+        mpsc::Sender<CandlestickGeneratorEvents<A>>,
+    >,
+    violation_handler: ViolationHandler,
+}
+
+impl<A: Asset> AsyncCandlestickGenerator<A> {
+    pub fn new(
+        asset_pair: AssetPair<A>,
+        interval: Interval,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        output_sender: mpsc::Sender<CandlestickGeneratorOutput<A>>,
+    ) -> Self {
+        Self {
+            aggregator: CandlestickAggregator::new(asset_pair, interval),
+            input_receiver,
+            output_sender: OutputSender::Closed(output_sender),
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    /// Generator reporting candlesticks in progress as well as closed ones, according to the update mode.
+    pub fn new_with_events(
+        asset_pair: AssetPair<A>,
+        interval: Interval,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        event_sender: mpsc::Sender<CandlestickGeneratorEvents<A>>,
+    ) -> Self {
+        Self {
+            aggregator: CandlestickAggregator::new(asset_pair, interval)
+                .with_update_mode(update_mode),
+            input_receiver,
+            output_sender: OutputSender::Events(event_sender),
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.aggregator = self.aggregator.with_gap_fill(gap_fill);
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.aggregator = self.aggregator.with_alignment(alignment);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.violation_handler = self.violation_handler.with_policy(policy);
+        self
+    }
+
+    /// skipped violations are reported to this sender.
+    pub fn with_error_sender(mut self, error_sender: mpsc::UnboundedSender<Error>) -> Self {
+        self.violation_handler = self.violation_handler.with_reporter(error_sender);
+        self
+    }
+
+    pub async fn start(mut self) -> Result<()> {
+        while let Some(input) = self.input_receiver.recv().await {
+            let is_terminate = input.is_terminate();
+            let events = apply_input(&mut self.aggregator, input);
+            if let Some(events) = self.violation_handler.handle(events)? {
+                self.output_sender.send(events, |c| c, |e| e).await?;
+            }
+            if is_terminate {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Time;
+    use crate::test_fixtures::{asset_pair, order_filled};
+    use crate::{CandlestickEvent, ViolationAction};
+
+    #[tokio::test]
+    async fn test_async_generator() {
+        let (input_sender, input_receiver) = mpsc::channel(16);
+        let (output_sender, mut output_receiver) = mpsc::channel(16);
+        let (error_sender, mut error_receiver) = mpsc::unbounded_channel();
+        let generator = AsyncCandlestickGenerator::new(
+            asset_pair(),
+            Interval::OneMinute,
+            input_receiver,
+            output_sender,
+        )
+        .with_policy(ViolationPolicy::new(ViolationAction::Skip))
+        .with_error_sender(error_sender);
+        let inputs = vec![
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 5),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(30, 50),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(30),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(60),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ];
+        for input in inputs {
+            input_sender.send(input).await.unwrap();
+        }
+        generator.start().await.unwrap();
+
+        assert!(matches!(
+            error_receiver.try_recv(),
+            Ok(Error::MisalignedTime { .. })
+        ));
+        let output = output_receiver.try_recv().unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].open_time, Time::new(0));
+        let data = output[0].data.as_ref().unwrap();
+        assert_eq!((data.open.num, data.high.num, data.close.num), (10, 30, 30));
+        assert!(output_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_async_events() {
+        let (input_sender, input_receiver) = mpsc::channel(16);
+        let (event_sender, mut event_receiver) = mpsc::channel(16);
+        let generator = AsyncCandlestickGenerator::new_with_events(
+            asset_pair(),
+            Interval::OneMinute,
+            UpdateMode::EveryTrade,
+            input_receiver,
+            event_sender,
+        );
+        for input in [
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 5),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 65),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ] {
+            input_sender.send(input).await.unwrap();
+        }
+        tokio::spawn(generator.start()).await.unwrap().unwrap();
+
+        let first = event_receiver.try_recv().unwrap();
+        assert!(matches!(&first[..], [CandlestickEvent::Update(_)]));
+        let second = event_receiver.try_recv().unwrap();
+        match &second[..] {
+            [CandlestickEvent::Final(closed), CandlestickEvent::Update(current)] => {
+                assert_eq!(closed.open_time, Time::new(0));
+                assert_eq!(current.open_time, Time::new(60));
+            }
+            events => panic!("unexpected events {:?}", events),
+        }
+        assert!(event_receiver.try_recv().is_err());
+    }
+}
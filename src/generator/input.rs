@@ -1,16 +1,40 @@
-use crate::models::{Asset, OrderFilled, Time};
+use crate::models::{Asset, AssetPair, OrderFilled, Time};
 
 #[derive(Debug)]
 pub enum CandlestickGeneratorInput<A: Asset> {
     /// start a new candlestick for live.
-    Tick {
-        open_time: Time,
-    },
+    Tick { open_time: Time },
     /// filled order event.
     /// This message could be used for both live and archive data.
-    OrderFilled {
-        open_time: Time,
-        order_filled: OrderFilled<A>,
-    },
-    Terminate,
+    /// The candlestick open time is derived from the execution time of the order.
+    OrderFilled { order_filled: OrderFilled<A> },
+    /// stop the generator, the candlestick in progress is emitted if `flush` is set.
+    Terminate { flush: bool },
+}
+
+impl<A: Asset> CandlestickGeneratorInput<A> {
+    pub fn is_terminate(&self) -> bool {
+        matches!(self, Self::Terminate { .. })
+    }
+}
+
+#[derive(Debug)]
+pub enum MultiPairCandlestickGeneratorInput<A: Asset> {
+    /// start a new candlestick for every registered asset pair.
+    Tick { open_time: Time },
+    /// filled order event, routed by its asset pair.
+    /// Unknown asset pairs are registered on their first filled order.
+    OrderFilled { order_filled: OrderFilled<A> },
+    /// start tracking the asset pair, so that it gets candlesticks even without filled orders.
+    Register { asset_pair: AssetPair<A> },
+    /// stop tracking the asset pair, the candlestick in progress is emitted.
+    Unregister { asset_pair: AssetPair<A> },
+    /// stop the generator, the candlesticks in progress are emitted if `flush` is set.
+    Terminate { flush: bool },
+}
+
+impl<A: Asset> MultiPairCandlestickGeneratorInput<A> {
+    pub fn is_terminate(&self) -> bool {
+        matches!(self, Self::Terminate { .. })
+    }
 }
@@ -0,0 +1,334 @@
+use super::{
+    finals, CandlestickAggregator, CandlestickGeneratorInput,
+    MultiIntervalCandlestickGeneratorEvents, MultiIntervalCandlestickGeneratorOutput, OutputSender,
+    ViolationHandler, ViolationPolicy,
+};
+use crate::models::{Alignment, Asset, AssetPair, Interval, OrderFilled, Time};
+use crate::{CandlestickEvent, Error, GapFill, Result, UpdateMode};
+use std::sync::mpsc;
+
+/// Generates candlesticks for several intervals of the same asset pair from a single input stream.
+/// Every input is validated and applied to all intervals before any of them is updated,
+/// so the intervals stay consistent with one another, even on arithmetic overflow.
+pub struct MultiIntervalCandlestickGenerator<A: Asset> {
+    aggregators: Vec<CandlestickAggregator<A>>,
+    input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+    output_sender: OutputSender<
+        mpsc::Sender<MultiIntervalCandlestickGeneratorOutput<A>>,
+        mpsc::Sender<MultiIntervalCandlestickGeneratorEvents<A>>,
+    >,
+    violation_handler: ViolationHandler,
+}
+
+impl<A: Asset> MultiIntervalCandlestickGenerator<A> {
+    pub fn new(
+        asset_pair: AssetPair<A>,
+        intervals: Vec<Interval>,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        output_sender: mpsc::Sender<MultiIntervalCandlestickGeneratorOutput<A>>,
+    ) -> Self {
+        Self::new_with_output(
+            asset_pair,
+            intervals,
+            UpdateMode::Disabled,
+            input_receiver,
+            OutputSender::Closed(output_sender),
+        )
+    }
+
+    /// Generator reporting candlesticks in progress of every interval as well as closed ones,
+    /// according to the update mode.
+    pub fn new_with_events(
+        asset_pair: AssetPair<A>,
+        intervals: Vec<Interval>,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        event_sender: mpsc::Sender<MultiIntervalCandlestickGeneratorEvents<A>>,
+    ) -> Self {
+        Self::new_with_output(
+            asset_pair,
+            intervals,
+            update_mode,
+            input_receiver,
+            OutputSender::Events(event_sender),
+        )
+    }
+
+    fn new_with_output(
+        asset_pair: AssetPair<A>,
+        intervals: Vec<Interval>,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<CandlestickGeneratorInput<A>>,
+        output_sender: OutputSender<
+            mpsc::Sender<MultiIntervalCandlestickGeneratorOutput<A>>,
+            mpsc::Sender<MultiIntervalCandlestickGeneratorEvents<A>>,
+        >,
+    ) -> Self {
+        let mut aggregators: Vec<CandlestickAggregator<A>> = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            if aggregators
+                .iter()
+                .all(|aggregator| aggregator.interval() != interval)
+            {
+                aggregators.push(
+                    CandlestickAggregator::new(asset_pair.clone(), interval)
+                        .with_update_mode(update_mode),
+                );
+            }
+        }
+        Self {
+            aggregators,
+            input_receiver,
+            output_sender,
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.aggregators = self
+            .aggregators
+            .into_iter()
+            .map(|aggregator| aggregator.with_gap_fill(gap_fill))
+            .collect();
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.aggregators = self
+            .aggregators
+            .into_iter()
+            .map(|aggregator| aggregator.with_alignment(alignment))
+            .collect();
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.violation_handler = self.violation_handler.with_policy(policy);
+        self
+    }
+
+    /// skipped violations are reported to this sender.
+    pub fn with_error_sender(mut self, error_sender: mpsc::Sender<Error>) -> Self {
+        self.violation_handler = self.violation_handler.with_reporter(error_sender);
+        self
+    }
+
+    /// `Tick` open time is floored to each interval, intervals whose bucket did not change are left as is.
+    pub fn start(mut self) -> Result<()> {
+        let aggregators = &mut self.aggregators;
+        let output_sender = &self.output_sender;
+        self.violation_handler.run(
+            self.input_receiver.iter(),
+            CandlestickGeneratorInput::is_terminate,
+            |input| {
+                let results = match input {
+                    CandlestickGeneratorInput::Tick { open_time } => {
+                        Self::tick(aggregators, open_time)?
+                    }
+                    CandlestickGeneratorInput::OrderFilled { order_filled } => {
+                        Self::order_filled(aggregators, &order_filled)?
+                    }
+                    CandlestickGeneratorInput::Terminate { flush } => {
+                        let mut results = vec![];
+                        if flush {
+                            for aggregator in aggregators.iter_mut() {
+                                let closed = finals(aggregator.flush());
+                                Self::push(&mut results, aggregator.interval(), closed);
+                            }
+                        }
+                        results
+                    }
+                };
+                for (interval, events) in results {
+                    output_sender.send_blocking(
+                        events,
+                        |closed| (interval, closed),
+                        |events| (interval, events),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    fn tick(
+        aggregators: &mut [CandlestickAggregator<A>],
+        time: Time,
+    ) -> Result<Vec<MultiIntervalCandlestickGeneratorEvents<A>>> {
+        let open_times = aggregators
+            .iter()
+            .map(|aggregator| aggregator.checked_floor(&time))
+            .collect::<Result<Vec<_>>>()?;
+        let is_new_bucket = |aggregator: &CandlestickAggregator<A>, open_time: Time| {
+            aggregator.open_time() != Some(open_time)
+        };
+        for (aggregator, &open_time) in aggregators.iter().zip(&open_times) {
+            if is_new_bucket(aggregator, open_time) {
+                aggregator.validate_tick(open_time)?;
+            }
+        }
+        let mut results = vec![];
+        for (aggregator, open_time) in aggregators.iter_mut().zip(open_times) {
+            if is_new_bucket(aggregator, open_time) {
+                let closed = finals(aggregator.tick(open_time));
+                Self::push(&mut results, aggregator.interval(), closed);
+            }
+        }
+        Ok(results)
+    }
+
+    fn order_filled(
+        aggregators: &mut [CandlestickAggregator<A>],
+        order_filled: &OrderFilled<A>,
+    ) -> Result<Vec<MultiIntervalCandlestickGeneratorEvents<A>>> {
+        for aggregator in aggregators.iter() {
+            aggregator.validate_trade(order_filled)?;
+        }
+        // an overflow in any interval must leave every interval untouched.
+        let prepared = aggregators
+            .iter()
+            .map(|aggregator| aggregator.prepare_trade(order_filled))
+            .collect::<Result<Vec<_>>>()?;
+        let mut results = vec![];
+        for (aggregator, data) in aggregators.iter_mut().zip(prepared) {
+            let mut events = finals(aggregator.commit_trade(order_filled, data));
+            events.extend(aggregator.update(order_filled));
+            Self::push(&mut results, aggregator.interval(), events);
+        }
+        Ok(results)
+    }
+
+    fn push(
+        results: &mut Vec<MultiIntervalCandlestickGeneratorEvents<A>>,
+        interval: Interval,
+        events: Vec<CandlestickEvent<A>>,
+    ) {
+        if !events.is_empty() {
+            results.push((interval, events));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Quantity;
+    use crate::test_fixtures::{asset_pair, order_filled};
+
+    #[test]
+    fn test_overflow_leaves_every_interval_untouched() {
+        let asset_pair = asset_pair();
+        let mut aggregators = vec![
+            CandlestickAggregator::new(asset_pair.clone(), Interval::OneMinute),
+            CandlestickAggregator::new(asset_pair, Interval::FiveMinutes),
+        ];
+        let mut whale = order_filled(1, 0);
+        whale.quantity = Quantity::new(u64::MAX, 0);
+        MultiIntervalCandlestickGenerator::order_filled(&mut aggregators, &whale).unwrap();
+
+        // a new bucket for the minute, but the five minute volume overflows.
+        let result =
+            MultiIntervalCandlestickGenerator::order_filled(&mut aggregators, &order_filled(2, 60));
+        assert!(matches!(result, Err(Error::Overflow)));
+        for aggregator in &aggregators {
+            let current = aggregator.current().unwrap();
+            assert_eq!(current.open_time, Time::new(0));
+            let data = current.data.as_ref().unwrap();
+            assert_eq!((data.close.num, data.trade_count), (1, 1));
+        }
+    }
+
+    #[test]
+    fn test_multi_interval() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let generator = MultiIntervalCandlestickGenerator::new(
+            asset_pair(),
+            vec![
+                Interval::OneMinute,
+                Interval::FiveMinutes,
+                Interval::OneMinute,
+            ],
+            input_receiver,
+            output_sender,
+        );
+        let inputs = vec![
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 0),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(30, 30),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 60),
+            },
+            CandlestickGeneratorInput::Tick {
+                open_time: Time::new(300),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ];
+        for input in inputs {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+
+        let outputs: Vec<MultiIntervalCandlestickGeneratorOutput<String>> =
+            output_receiver.try_iter().collect();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[0].0, Interval::OneMinute);
+        assert_eq!(outputs[0].1[0].data.as_ref().unwrap().high.num, 30);
+        assert_eq!(outputs[1].0, Interval::OneMinute);
+        assert_eq!(outputs[1].1[0].open_time, Time::new(60));
+        assert_eq!(outputs[2].0, Interval::FiveMinutes);
+        let data = outputs[2].1[0].data.as_ref().unwrap();
+        assert_eq!((data.open.num, data.high.num, data.close.num), (10, 30, 20));
+    }
+
+    #[test]
+    fn test_multi_interval_events() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let generator = MultiIntervalCandlestickGenerator::new_with_events(
+            asset_pair(),
+            vec![Interval::OneMinute, Interval::FiveMinutes],
+            UpdateMode::EveryTrade,
+            input_receiver,
+            event_sender,
+        );
+        for input in [
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(10, 0),
+            },
+            CandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled(20, 60),
+            },
+            CandlestickGeneratorInput::Terminate { flush: false },
+        ] {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+
+        let events: Vec<(Interval, Vec<&str>)> = event_receiver
+            .try_iter()
+            .map(|(interval, events)| {
+                let kinds = events
+                    .iter()
+                    .map(|event| match event {
+                        CandlestickEvent::Update(_) => "update",
+                        CandlestickEvent::Final(_) => "final",
+                    })
+                    .collect();
+                (interval, kinds)
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (Interval::OneMinute, vec!["update"]),
+                (Interval::FiveMinutes, vec!["update"]),
+                (Interval::OneMinute, vec!["final", "update"]),
+                (Interval::FiveMinutes, vec!["update"]),
+            ]
+        );
+    }
+}
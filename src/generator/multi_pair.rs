@@ -0,0 +1,443 @@
+use super::{
+    finals, CandlestickAggregator, MultiPairCandlestickGeneratorEvents,
+    MultiPairCandlestickGeneratorInput, MultiPairCandlestickGeneratorOutput, OutputSender,
+    ViolationHandler, ViolationPolicy,
+};
+use crate::models::{Alignment, Asset, AssetPair, Interval, OrderFilled, Time};
+use crate::{CandlestickEvent, Error, GapFill, Result, UpdateMode};
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+/// settings every asset pair gets when it is registered.
+#[derive(Copy, Clone)]
+struct PairSettings {
+    interval: Interval,
+    gap_fill: GapFill,
+    alignment: Alignment,
+    update_mode: UpdateMode,
+}
+
+/// per-pair aggregators in registration order, so that outputs are reproducible.
+struct Aggregators<A: Asset> {
+    aggregators: Vec<CandlestickAggregator<A>>,
+    indexes: HashMap<AssetPair<A>, usize>,
+}
+
+impl<A: Asset> Aggregators<A> {
+    fn new() -> Self {
+        Self {
+            aggregators: vec![],
+            indexes: HashMap::new(),
+        }
+    }
+
+    fn get_mut(&mut self, asset_pair: &AssetPair<A>) -> Option<&mut CandlestickAggregator<A>> {
+        let index = *self.indexes.get(asset_pair)?;
+        Some(&mut self.aggregators[index])
+    }
+
+    /// the aggregator must not be registered yet.
+    fn insert(&mut self, aggregator: CandlestickAggregator<A>) {
+        let index = self.aggregators.len();
+        self.indexes.insert(aggregator.asset_pair().clone(), index);
+        self.aggregators.push(aggregator);
+    }
+
+    fn remove(&mut self, asset_pair: &AssetPair<A>) -> Option<CandlestickAggregator<A>> {
+        let index = self.indexes.remove(asset_pair)?;
+        for later in self.indexes.values_mut().filter(|later| **later > index) {
+            *later -= 1;
+        }
+        Some(self.aggregators.remove(index))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut CandlestickAggregator<A>> {
+        self.aggregators.iter_mut()
+    }
+}
+
+/// Generates candlesticks of a single interval for many asset pairs from a single input stream.
+/// Filled orders are routed by their asset pair to a per-pair aggregator created on demand.
+/// Outputs of several asset pairs for a single input come in registration order.
+pub struct MultiPairCandlestickGenerator<A: Asset> {
+    settings: PairSettings,
+    aggregators: Aggregators<A>,
+    input_receiver: mpsc::Receiver<MultiPairCandlestickGeneratorInput<A>>,
+    output_sender: OutputSender<
+        mpsc::Sender<MultiPairCandlestickGeneratorOutput<A>>,
+        mpsc::Sender<MultiPairCandlestickGeneratorEvents<A>>,
+    >,
+    violation_handler: ViolationHandler,
+}
+
+impl<A: Asset> MultiPairCandlestickGenerator<A> {
+    pub fn new(
+        interval: Interval,
+        input_receiver: mpsc::Receiver<MultiPairCandlestickGeneratorInput<A>>,
+        output_sender: mpsc::Sender<MultiPairCandlestickGeneratorOutput<A>>,
+    ) -> Self {
+        Self::new_with_output(
+            interval,
+            UpdateMode::Disabled,
+            input_receiver,
+            OutputSender::Closed(output_sender),
+        )
+    }
+
+    /// Generator reporting candlesticks in progress of every asset pair as well as closed ones,
+    /// according to the update mode.
+    pub fn new_with_events(
+        interval: Interval,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<MultiPairCandlestickGeneratorInput<A>>,
+        event_sender: mpsc::Sender<MultiPairCandlestickGeneratorEvents<A>>,
+    ) -> Self {
+        Self::new_with_output(
+            interval,
+            update_mode,
+            input_receiver,
+            OutputSender::Events(event_sender),
+        )
+    }
+
+    fn new_with_output(
+        interval: Interval,
+        update_mode: UpdateMode,
+        input_receiver: mpsc::Receiver<MultiPairCandlestickGeneratorInput<A>>,
+        output_sender: OutputSender<
+            mpsc::Sender<MultiPairCandlestickGeneratorOutput<A>>,
+            mpsc::Sender<MultiPairCandlestickGeneratorEvents<A>>,
+        >,
+    ) -> Self {
+        Self {
+            settings: PairSettings {
+                interval,
+                gap_fill: GapFill::Empty,
+                alignment: Alignment::default(),
+                update_mode,
+            },
+            aggregators: Aggregators::new(),
+            input_receiver,
+            output_sender,
+            violation_handler: ViolationHandler::default(),
+        }
+    }
+
+    /// applies to asset pairs registered afterwards.
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.settings.gap_fill = gap_fill;
+        self
+    }
+
+    /// applies to asset pairs registered afterwards, ticks are validated against it.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.settings.alignment = alignment;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.violation_handler = self.violation_handler.with_policy(policy);
+        self
+    }
+
+    /// skipped violations are reported to this sender.
+    pub fn with_error_sender(mut self, error_sender: mpsc::Sender<Error>) -> Self {
+        self.violation_handler = self.violation_handler.with_reporter(error_sender);
+        self
+    }
+
+    /// `Tick` leaves asset pairs which already started the bucket, e.g. by a filled order, as is.
+    /// An asset pair the tick regresses is a violation of its own, the other pairs still tick.
+    pub fn start(mut self) -> Result<()> {
+        let aggregators = &mut self.aggregators;
+        let output_sender = &self.output_sender;
+        let settings = &self.settings;
+        let violation_handler = &self.violation_handler;
+        violation_handler.run(
+            self.input_receiver.iter(),
+            MultiPairCandlestickGeneratorInput::is_terminate,
+            |input| {
+                let mut violations = vec![];
+                let results = match input {
+                    MultiPairCandlestickGeneratorInput::Tick { open_time } => {
+                        let (results, pair_violations) =
+                            Self::tick(aggregators, settings, open_time)?;
+                        violations = pair_violations;
+                        results
+                    }
+                    MultiPairCandlestickGeneratorInput::OrderFilled { order_filled } => {
+                        Self::order_filled(aggregators, settings, order_filled)?
+                    }
+                    MultiPairCandlestickGeneratorInput::Register { asset_pair } => {
+                        Self::register(aggregators, settings, asset_pair);
+                        vec![]
+                    }
+                    MultiPairCandlestickGeneratorInput::Unregister { asset_pair } => {
+                        match aggregators.remove(&asset_pair) {
+                            Some(mut aggregator) => {
+                                Self::tag(asset_pair, finals(aggregator.flush()))
+                            }
+                            None => vec![],
+                        }
+                    }
+                    MultiPairCandlestickGeneratorInput::Terminate { flush } => {
+                        let mut results = vec![];
+                        if flush {
+                            for aggregator in aggregators.iter_mut() {
+                                let closed = finals(aggregator.flush());
+                                results.extend(Self::tag(aggregator.asset_pair().clone(), closed));
+                            }
+                        }
+                        results
+                    }
+                };
+                for (asset_pair, events) in results {
+                    output_sender.send_blocking(
+                        events,
+                        |closed| (asset_pair.clone(), closed),
+                        |events| (asset_pair.clone(), events),
+                    )?;
+                }
+                for violation in violations {
+                    violation_handler.handle::<()>(Err(violation))?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// events of the asset pairs the tick applies to, and the violations of the others.
+    fn tick(
+        aggregators: &mut Aggregators<A>,
+        settings: &PairSettings,
+        open_time: Time,
+    ) -> Result<(Vec<MultiPairCandlestickGeneratorEvents<A>>, Vec<Error>)> {
+        let interval = settings.interval;
+        if !interval.is_valid_time_aligned(&open_time, &settings.alignment) {
+            return Err(Error::MisalignedTime {
+                interval,
+                time: open_time,
+            });
+        }
+        let (mut results, mut violations) = (vec![], vec![]);
+        for aggregator in aggregators.iter_mut() {
+            if aggregator.open_time() == Some(open_time) {
+                continue;
+            }
+            match aggregator.validate_tick(open_time) {
+                Ok(()) => {
+                    let closed = finals(aggregator.tick(open_time));
+                    results.extend(Self::tag(aggregator.asset_pair().clone(), closed));
+                }
+                Err(violation) => violations.push(violation),
+            }
+        }
+        Ok((results, violations))
+    }
+
+    /// an asset pair seen for the first time is registered once its first trade succeeds.
+    fn order_filled(
+        aggregators: &mut Aggregators<A>,
+        settings: &PairSettings,
+        order_filled: OrderFilled<A>,
+    ) -> Result<Vec<MultiPairCandlestickGeneratorEvents<A>>> {
+        let asset_pair = order_filled.asset_pair.clone();
+        let events = match aggregators.get_mut(&asset_pair) {
+            Some(aggregator) => aggregator.on_trade_events(&order_filled)?,
+            None => {
+                let mut aggregator = Self::aggregator(settings, asset_pair.clone());
+                let events = aggregator.on_trade_events(&order_filled)?;
+                aggregators.insert(aggregator);
+                events
+            }
+        };
+        Ok(Self::tag(asset_pair, events))
+    }
+
+    fn register(
+        aggregators: &mut Aggregators<A>,
+        settings: &PairSettings,
+        asset_pair: AssetPair<A>,
+    ) {
+        if aggregators.get_mut(&asset_pair).is_none() {
+            aggregators.insert(Self::aggregator(settings, asset_pair));
+        }
+    }
+
+    fn aggregator(settings: &PairSettings, asset_pair: AssetPair<A>) -> CandlestickAggregator<A> {
+        CandlestickAggregator::new(asset_pair, settings.interval)
+            .with_gap_fill(settings.gap_fill)
+            .with_alignment(settings.alignment)
+            .with_update_mode(settings.update_mode)
+    }
+
+    fn tag(
+        asset_pair: AssetPair<A>,
+        events: Vec<CandlestickEvent<A>>,
+    ) -> Vec<MultiPairCandlestickGeneratorEvents<A>> {
+        if events.is_empty() {
+            vec![]
+        } else {
+            vec![(asset_pair, events)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{asset_pair_for, order_filled_for};
+    use crate::ViolationAction;
+
+    #[test]
+    fn test_multi_pair() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let generator =
+            MultiPairCandlestickGenerator::new(Interval::OneMinute, input_receiver, output_sender);
+        let inputs = vec![
+            MultiPairCandlestickGeneratorInput::Register {
+                asset_pair: asset_pair_for("ETH"),
+            },
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("BTC", 10, 0),
+            },
+            MultiPairCandlestickGeneratorInput::Tick {
+                open_time: Time::new(0),
+            },
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("BTC", 20, 70),
+            },
+            MultiPairCandlestickGeneratorInput::Tick {
+                open_time: Time::new(60),
+            },
+            MultiPairCandlestickGeneratorInput::Unregister {
+                asset_pair: asset_pair_for("BTC"),
+            },
+            MultiPairCandlestickGeneratorInput::Terminate { flush: false },
+        ];
+        for input in inputs {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+
+        let mut outputs: Vec<MultiPairCandlestickGeneratorOutput<String>> =
+            output_receiver.try_iter().collect();
+        assert_eq!(outputs.len(), 3);
+        let unregistered = outputs.pop().unwrap();
+        assert_eq!(unregistered.0, asset_pair_for("BTC"));
+        assert_eq!(unregistered.1[0].open_time, Time::new(60));
+        assert_eq!(outputs[0].0, asset_pair_for("BTC"));
+        assert_eq!(outputs[0].1[0].data.as_ref().unwrap().close.num, 10);
+        assert_eq!(outputs[1].0, asset_pair_for("ETH"));
+        assert!(outputs[1].1[0].data.is_none());
+    }
+
+    #[test]
+    fn test_multi_pair_events() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let generator = MultiPairCandlestickGenerator::new_with_events(
+            Interval::OneMinute,
+            UpdateMode::EveryTrade,
+            input_receiver,
+            event_sender,
+        );
+        for input in [
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("BTC", 10, 0),
+            },
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("ETH", 20, 10),
+            },
+            MultiPairCandlestickGeneratorInput::Tick {
+                open_time: Time::new(60),
+            },
+            MultiPairCandlestickGeneratorInput::Terminate { flush: false },
+        ] {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+
+        let events: Vec<MultiPairCandlestickGeneratorEvents<String>> =
+            event_receiver.try_iter().collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].0, asset_pair_for("BTC"));
+        assert!(matches!(&events[0].1[..], [CandlestickEvent::Update(_)]));
+        assert_eq!(events[1].0, asset_pair_for("ETH"));
+        assert!(matches!(&events[1].1[..], [CandlestickEvent::Update(_)]));
+        assert!(events[2..]
+            .iter()
+            .all(|(_, events)| matches!(&events[..], [CandlestickEvent::Final(_)])));
+    }
+
+    #[test]
+    fn test_tick_violation_of_one_pair() {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let (output_sender, output_receiver) = mpsc::channel();
+        let (error_sender, error_receiver) = mpsc::channel();
+        let generator =
+            MultiPairCandlestickGenerator::new(Interval::OneMinute, input_receiver, output_sender)
+                .with_policy(ViolationPolicy::new(ViolationAction::Skip))
+                .with_error_sender(error_sender);
+        let inputs = vec![
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("SOL", 30, 0),
+            },
+            // BTC is a bucket ahead of the tick.
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("BTC", 10, 120),
+            },
+            MultiPairCandlestickGeneratorInput::OrderFilled {
+                order_filled: order_filled_for("ETH", 20, 0),
+            },
+            MultiPairCandlestickGeneratorInput::Tick {
+                open_time: Time::new(60),
+            },
+            MultiPairCandlestickGeneratorInput::Terminate { flush: true },
+        ];
+        for input in inputs {
+            input_sender.send(input).unwrap();
+        }
+        generator.start().unwrap();
+
+        let outputs: Vec<(String, i64)> = output_receiver
+            .try_iter()
+            .map(|(asset_pair, closed)| (asset_pair.base_asset, closed[0].open_time.value()))
+            .collect();
+        let expected = vec![
+            ("SOL", 0),
+            ("ETH", 0),
+            // flushed in registration order
+            ("SOL", 60),
+            ("BTC", 120),
+            ("ETH", 60),
+        ];
+        let expected: Vec<(String, i64)> = expected
+            .into_iter()
+            .map(|(base_asset, open_time)| (base_asset.to_owned(), open_time))
+            .collect();
+        assert_eq!(outputs, expected);
+        let errors: Vec<Error> = error_receiver.try_iter().collect();
+        assert!(matches!(&errors[..], [Error::RegressingTime { .. }]));
+    }
+
+    #[test]
+    fn test_rejected_trade_leaves_no_pair() {
+        let settings = PairSettings {
+            interval: Interval::OneMinute,
+            gap_fill: GapFill::Empty,
+            alignment: Alignment::default(),
+            update_mode: UpdateMode::Disabled,
+        };
+        let mut aggregators = Aggregators::new();
+        let mut whale = order_filled_for("BTC", 1, 0);
+        whale.price = crate::models::Price::new(u64::MAX, 0);
+        whale.quantity = crate::models::Quantity::new(2, 0);
+        let result =
+            MultiPairCandlestickGenerator::order_filled(&mut aggregators, &settings, whale);
+        assert!(matches!(result, Err(Error::Overflow)));
+        assert!(aggregators.get_mut(&asset_pair_for("BTC")).is_none());
+        assert_eq!(aggregators.iter_mut().count(), 0);
+    }
+}
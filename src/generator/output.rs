@@ -1,3 +1,19 @@
-use crate::models::Candlestick;
+use crate::models::{AssetPair, Candlestick, Interval};
+use crate::CandlestickEvent;
 
 pub type CandlestickGeneratorOutput<A> = Vec<Candlestick<A>>;
+
+/// closed and, depending on the update mode, provisional candlesticks.
+pub type CandlestickGeneratorEvents<A> = Vec<CandlestickEvent<A>>;
+
+/// closed candlesticks tagged with the interval they belong to.
+pub type MultiIntervalCandlestickGeneratorOutput<A> = (Interval, Vec<Candlestick<A>>);
+
+/// closed candlesticks tagged with the asset pair they belong to.
+pub type MultiPairCandlestickGeneratorOutput<A> = (AssetPair<A>, Vec<Candlestick<A>>);
+
+/// closed and provisional candlesticks tagged with the interval they belong to.
+pub type MultiIntervalCandlestickGeneratorEvents<A> = (Interval, Vec<CandlestickEvent<A>>);
+
+/// closed and provisional candlesticks tagged with the asset pair they belong to.
+pub type MultiPairCandlestickGeneratorEvents<A> = (AssetPair<A>, Vec<CandlestickEvent<A>>);
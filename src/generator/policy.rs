@@ -0,0 +1,116 @@
+use crate::{Error, Result};
+
+/// What the generator does when an input violates its expectations.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ViolationAction {
+    /// drop the input, report the error and keep going.
+    Skip,
+    /// stop the generator and return the error.
+    Stop,
+}
+
+/// Action taken for each kind of input violation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ViolationPolicy {
+    pub misaligned_time: ViolationAction,
+    pub regressing_time: ViolationAction,
+    pub asset_pair_mismatch: ViolationAction,
+}
+
+impl ViolationPolicy {
+    pub fn new(action: ViolationAction) -> Self {
+        Self {
+            misaligned_time: action,
+            regressing_time: action,
+            asset_pair_mismatch: action,
+        }
+    }
+
+    pub fn action(&self, error: &Error) -> ViolationAction {
+        match error {
+            Error::MisalignedTime { .. } => self.misaligned_time,
+            Error::RegressingTime { .. } => self.regressing_time,
+            Error::AssetPairMismatch { .. } => self.asset_pair_mismatch,
+            _ => ViolationAction::Stop,
+        }
+    }
+}
+
+impl Default for ViolationPolicy {
+    fn default() -> Self {
+        Self::new(ViolationAction::Stop)
+    }
+}
+
+/// where skipped violations are reported.
+///
+/// reporting is best effort, errors are dropped once nobody is listening any more.
+pub(crate) trait ErrorReporter: Send {
+    fn report(&self, error: Error);
+}
+
+impl ErrorReporter for std::sync::mpsc::Sender<Error> {
+    fn report(&self, error: Error) {
+        let _ = self.send(error);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ErrorReporter for tokio::sync::mpsc::UnboundedSender<Error> {
+    fn report(&self, error: Error) {
+        let _ = self.send(error);
+    }
+}
+
+/// applies a `ViolationPolicy` to errors raised while handling an input.
+#[derive(Default)]
+pub(crate) struct ViolationHandler {
+    policy: ViolationPolicy,
+    reporter: Option<Box<dyn ErrorReporter>>,
+}
+
+impl ViolationHandler {
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_reporter(mut self, reporter: impl ErrorReporter + 'static) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    /// `None` for a skipped and reported error, the error itself if it stops the generator.
+    pub fn handle<T>(&self, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => match self.policy.action(&error) {
+                ViolationAction::Skip => {
+                    if let Some(reporter) = &self.reporter {
+                        reporter.report(error);
+                    }
+                    Ok(None)
+                }
+                ViolationAction::Stop => Err(error),
+            },
+        }
+    }
+
+    /// feeds every input to `step` up to and including the terminating one,
+    /// errors of `step` go through `handle`.
+    pub fn run<I>(
+        &self,
+        inputs: impl IntoIterator<Item = I>,
+        is_terminate: impl Fn(&I) -> bool,
+        mut step: impl FnMut(I) -> Result<()>,
+    ) -> Result<()> {
+        for input in inputs {
+            let is_terminate = is_terminate(&input);
+            self.handle(step(input))?;
+            if is_terminate {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
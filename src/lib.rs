@@ -1,9 +1,21 @@
-mod models;
+mod aggregator;
+#[cfg(feature = "csv")]
+mod csv;
 mod error;
 mod generator;
+mod models;
+mod resample;
+#[cfg(feature = "serde")]
+pub mod serialization;
+#[cfg(test)]
+mod test_fixtures;
 
-pub use models::*;
+#[cfg(feature = "csv")]
+pub use crate::csv::{Column, CsvFormat, CsvReader, CsvWriter, TimeFormat};
+pub use aggregator::{CandlestickAggregator, CandlestickEvent, GapFill, UpdateMode};
 pub use error::Error;
 pub use generator::*;
+pub use models::*;
+pub use resample::{resample, CandlestickResampler, Resampled};
 
 pub type Result<T> = core::result::Result<T, Error>;
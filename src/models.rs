@@ -1,19 +1,25 @@
+mod alignment;
 mod asset;
 mod asset_pair;
 mod candlestick;
+mod fixed_point;
 mod interval;
 mod order_filled;
 mod price;
 mod quantity;
+mod side;
 mod time;
 mod volume;
 
+pub use alignment::*;
 pub use asset::*;
 pub use asset_pair::*;
 pub use candlestick::*;
+pub use fixed_point::{DecimalOptions, RoundingMode, TrailingZeros};
 pub use interval::*;
 pub use order_filled::*;
 pub use price::*;
 pub use quantity::*;
+pub use side::*;
 pub use time::*;
 pub use volume::*;
@@ -0,0 +1,390 @@
+use super::Time;
+use crate::{Error, Result};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+
+/// time zone whose local calendar the day, week, month and year buckets follow.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TimeZone {
+    Utc,
+    Fixed(FixedOffset),
+    /// IANA time zone, daylight saving time transitions included.
+    #[cfg(feature = "chrono-tz")]
+    Iana(chrono_tz::Tz),
+}
+
+/// where calendar buckets start.
+///
+/// fixed width intervals, from `OneSecond` through `TwelveHours`, `ThreeDays` and `Custom`,
+/// are always aligned to the unix epoch and ignore the alignment.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Alignment {
+    pub time_zone: TimeZone,
+    /// first day of `OneWeek` buckets.
+    pub week_start: Weekday,
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Self {
+            time_zone: TimeZone::Utc,
+            week_start: Weekday::Sun,
+        }
+    }
+}
+
+impl Alignment {
+    pub fn with_time_zone(mut self, time_zone: TimeZone) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// `Weekday::Mon` for ISO 8601 weeks.
+    pub fn with_week_start(mut self, week_start: Weekday) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    /// first date of the week containing the date.
+    pub(crate) fn start_of_week(&self, date: NaiveDate) -> NaiveDate {
+        let weekday = date.weekday().num_days_from_monday();
+        let days = (7 + weekday - self.week_start.num_days_from_monday()) % 7;
+        date - chrono::Duration::days(i64::from(days))
+    }
+
+    /// calendar date of the time in the time zone.
+    pub(crate) fn local_date(&self, time: &Time) -> NaiveDate {
+        match self.time_zone {
+            TimeZone::Utc => local_date(&Utc, time),
+            TimeZone::Fixed(offset) => local_date(&offset, time),
+            #[cfg(feature = "chrono-tz")]
+            TimeZone::Iana(tz) => local_date(&tz, time),
+        }
+    }
+
+    /// distinct UTC offsets in seconds the time zone has used from 1970 through 2037,
+    /// time zone rules past 2037 repeat those of the years before.
+    pub(crate) fn utc_offsets(&self) -> Vec<i32> {
+        match self.time_zone {
+            TimeZone::Utc => vec![0],
+            TimeZone::Fixed(offset) => vec![offset.local_minus_utc()],
+            #[cfg(feature = "chrono-tz")]
+            TimeZone::Iana(tz) => iana_offsets(tz),
+        }
+    }
+
+    /// first instant of the date in the time zone.
+    pub(crate) fn start_of_day(&self, date: NaiveDate) -> Result<Time> {
+        match self.time_zone {
+            TimeZone::Utc => start_of_day(&Utc, date),
+            TimeZone::Fixed(offset) => start_of_day(&offset, date),
+            #[cfg(feature = "chrono-tz")]
+            TimeZone::Iana(tz) => start_of_day(&tz, date),
+        }
+    }
+}
+
+fn local_date<Tz: chrono::TimeZone>(tz: &Tz, time: &Time) -> NaiveDate {
+    let utc = NaiveDateTime::from(*time);
+    tz.from_utc_datetime(&utc).date_naive()
+}
+
+fn start_of_day<Tz: chrono::TimeZone>(tz: &Tz, date: NaiveDate) -> Result<Time> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    let start = match tz.from_local_datetime(&midnight).earliest() {
+        Some(start) => start.with_timezone(&Utc),
+        // a transition skipped local midnight, the day starts when the clocks jump.
+        None => first_instant_from(tz, midnight).ok_or_else(|| Error::TimeOutOfRange {
+            value: midnight.to_string(),
+        })?,
+    };
+    Time::try_from(start)
+}
+
+/// first whole second whose local time is `local` or later.
+///
+/// offsets are below a day, so the instant is searched within a day on either side.
+fn first_instant_from<Tz: chrono::TimeZone>(
+    tz: &Tz,
+    local: NaiveDateTime,
+) -> Option<DateTime<Utc>> {
+    const SECONDS_PER_DAY: i64 = 86_400;
+    let reached = |timestamp: i64| {
+        DateTime::from_timestamp(timestamp, 0)
+            .map(|utc| tz.from_utc_datetime(&utc.naive_utc()).naive_local() >= local)
+    };
+    let timestamp = local.and_utc().timestamp();
+    let (mut before, mut after) = (timestamp - SECONDS_PER_DAY, timestamp + SECONDS_PER_DAY);
+    while after - before > 1 {
+        let middle = before + (after - before) / 2;
+        if reached(middle)? {
+            after = middle;
+        } else {
+            before = middle;
+        }
+    }
+    DateTime::from_timestamp(after, 0)
+}
+
+/// offsets are sampled daily, and the transitions between two different samples are searched
+/// for the offsets in between. Offsets are computed once per time zone.
+///
+/// an offset in effect for less than a day between two samples of the same offset is missed.
+#[cfg(feature = "chrono-tz")]
+fn iana_offsets(tz: chrono_tz::Tz) -> Vec<i32> {
+    use chrono::{Offset, TimeZone as _};
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    static OFFSETS: OnceLock<Mutex<HashMap<chrono_tz::Tz, Vec<i32>>>> = OnceLock::new();
+    const SECONDS_PER_DAY: i64 = 86_400;
+    let offset_at = |timestamp: i64| {
+        let utc = DateTime::from_timestamp(timestamp, 0).unwrap().naive_utc();
+        tz.offset_from_utc_datetime(&utc).fix().local_minus_utc()
+    };
+    let mut offsets = OFFSETS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    offsets
+        .entry(tz)
+        .or_insert_with(|| {
+            let start = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            let end = NaiveDate::from_ymd_opt(2038, 1, 1).unwrap();
+            let end = end.and_time(NaiveTime::MIN).and_utc().timestamp();
+            let mut timestamp = start.and_time(NaiveTime::MIN).and_utc().timestamp();
+            let mut offset = offset_at(timestamp);
+            let mut offsets = vec![offset];
+            while timestamp < end {
+                let next = timestamp + SECONDS_PER_DAY;
+                let next_offset = offset_at(next);
+                // walks every transition of the day, one bisection each.
+                while offset != next_offset {
+                    let (mut before, mut after) = (timestamp, next);
+                    while after - before > 1 {
+                        let middle = before + (after - before) / 2;
+                        if offset_at(middle) == offset {
+                            before = middle;
+                        } else {
+                            after = middle;
+                        }
+                    }
+                    (timestamp, offset) = (after, offset_at(after));
+                    if !offsets.contains(&offset) {
+                        offsets.push(offset);
+                    }
+                }
+                timestamp = next;
+            }
+            offsets
+        })
+        .clone()
+}
+
+impl From<FixedOffset> for TimeZone {
+    fn from(offset: FixedOffset) -> Self {
+        Self::Fixed(offset)
+    }
+}
+
+#[cfg(feature = "chrono-tz")]
+impl From<chrono_tz::Tz> for TimeZone {
+    fn from(tz: chrono_tz::Tz) -> Self {
+        Self::Iana(tz)
+    }
+}
+
+impl From<TimeZone> for Alignment {
+    fn from(time_zone: TimeZone) -> Self {
+        Self::default().with_time_zone(time_zone)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Interval;
+    use std::str::FromStr;
+
+    fn time(value: &str) -> Time {
+        Time::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_fixed_offset() {
+        let alignment = Alignment::from(TimeZone::Fixed(FixedOffset::east_opt(8 * 3600).unwrap()));
+        // 2023-02-01T01:00:00+08:00, a Wednesday
+        let trade_time = time("2023-01-31T17:00:00Z");
+        let tests = vec![
+            (
+                Interval::OneHour,
+                "2023-01-31T17:00:00Z",
+                "2023-01-31T18:00:00Z",
+            ),
+            (
+                Interval::OneDay,
+                "2023-01-31T16:00:00Z",
+                "2023-02-01T16:00:00Z",
+            ),
+            (
+                Interval::OneWeek,
+                "2023-01-28T16:00:00Z",
+                "2023-02-04T16:00:00Z",
+            ),
+            (
+                Interval::OneMonth,
+                "2023-01-31T16:00:00Z",
+                "2023-02-28T16:00:00Z",
+            ),
+            (
+                Interval::OneYear,
+                "2022-12-31T16:00:00Z",
+                "2023-12-31T16:00:00Z",
+            ),
+        ];
+        for (interval, floor, next) in tests {
+            let floor = time(floor);
+            assert_eq!(interval.floor_aligned(&trade_time, &alignment), floor);
+            assert!(interval.is_valid_time_aligned(&floor, &alignment));
+            assert_eq!(interval.next_aligned(floor, &alignment), time(next));
+        }
+        assert!(!Interval::OneDay.is_valid_time_aligned(&time("2023-02-01T00:00:00Z"), &alignment));
+    }
+
+    #[test]
+    fn test_week_start() {
+        let tests = vec![
+            // Sunday of the first week of 2023
+            (Weekday::Mon, "2023-01-01T12:00:00Z", "2022-12-26T00:00:00Z"),
+            (Weekday::Sun, "2023-01-01T12:00:00Z", "2023-01-01T00:00:00Z"),
+            (Weekday::Sat, "2023-01-01T12:00:00Z", "2022-12-31T00:00:00Z"),
+            // Tuesday, ISO week 1 of 2025
+            (Weekday::Mon, "2024-12-31T23:59:59Z", "2024-12-30T00:00:00Z"),
+            (Weekday::Wed, "2024-12-31T23:59:59Z", "2024-12-25T00:00:00Z"),
+            (Weekday::Tue, "2024-12-31T23:59:59Z", "2024-12-31T00:00:00Z"),
+            // Wednesday, the first day of 2020
+            (Weekday::Sat, "2020-01-01T00:00:00Z", "2019-12-28T00:00:00Z"),
+            (Weekday::Thu, "2020-01-01T00:00:00Z", "2019-12-26T00:00:00Z"),
+        ];
+        for (week_start, trade_time, floor) in tests {
+            let alignment = Alignment::default().with_week_start(week_start);
+            let floor = time(floor);
+            let actual = Interval::OneWeek.floor_aligned(&time(trade_time), &alignment);
+            assert_eq!(actual, floor, "{:?} {}", week_start, trade_time);
+            assert!(Interval::OneWeek.is_valid_time_aligned(&floor, &alignment));
+            let next = Interval::OneWeek.next_aligned(floor, &alignment);
+            assert_eq!(next - floor, crate::models::Duration::from_weeks(1));
+            assert!(Interval::OneWeek.is_valid_time_aligned(&next, &alignment));
+        }
+        let iso = Alignment::default().with_week_start(Weekday::Mon);
+        assert!(!Interval::OneWeek.is_valid_time_aligned(&time("2023-01-01T00:00:00Z"), &iso));
+        assert!(Interval::OneWeek.is_valid_time_aligned(&time("2023-01-02T00:00:00Z"), &iso));
+    }
+
+    #[test]
+    fn test_divisor_in_time_zone() {
+        let kolkata = Alignment::from(TimeZone::Fixed(
+            FixedOffset::east_opt(5 * 3600 + 1800).unwrap(),
+        ));
+        assert!(Interval::ThirtyMinutes.is_divisor_of(&Interval::OneDay, &kolkata));
+        assert!(!Interval::OneHour.is_divisor_of(&Interval::OneDay, &kolkata));
+
+        #[cfg(feature = "chrono-tz")]
+        {
+            let divides = |source: Interval, tz: chrono_tz::Tz| {
+                source.is_divisor_of(&Interval::OneDay, &Alignment::from(TimeZone::Iana(tz)))
+            };
+            assert!(divides(Interval::OneHour, chrono_tz::Asia::Tokyo));
+            assert!(divides(Interval::OneHour, chrono_tz::America::New_York));
+            assert!(!divides(Interval::TwoHours, chrono_tz::America::New_York));
+            assert!(!divides(Interval::OneHour, chrono_tz::Asia::Kolkata));
+            assert!(divides(Interval::ThirtyMinutes, chrono_tz::Asia::Kolkata));
+            // +10:30 in winter, +11:00 in summer
+            assert!(!divides(Interval::OneHour, chrono_tz::Australia::Lord_Howe));
+            assert!(divides(
+                Interval::ThirtyMinutes,
+                chrono_tz::Australia::Lord_Howe
+            ));
+        }
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn test_utc_offsets() {
+        let offsets = |tz: chrono_tz::Tz| {
+            let mut offsets = Alignment::from(TimeZone::Iana(tz)).utc_offsets();
+            offsets.sort();
+            offsets
+        };
+        assert_eq!(offsets(chrono_tz::Asia::Tokyo), vec![9 * 3600]);
+        assert_eq!(
+            offsets(chrono_tz::America::New_York),
+            vec![-5 * 3600, -4 * 3600]
+        );
+        // +10:00 until 1981, then +10:30 in winter and +11:00 or +11:30 in summer.
+        assert_eq!(
+            offsets(chrono_tz::Australia::Lord_Howe),
+            vec![10 * 3600, 10 * 3600 + 1800, 11 * 3600, 11 * 3600 + 1800]
+        );
+        // computed once, the same offsets again.
+        assert_eq!(
+            offsets(chrono_tz::America::New_York),
+            vec![-5 * 3600, -4 * 3600]
+        );
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn test_daylight_saving_time() {
+        let new_york = Alignment::from(TimeZone::Iana(chrono_tz::America::New_York));
+        let days: Vec<Time> = Interval::OneDay
+            .range_aligned(
+                time("2023-03-11T12:00:00Z"),
+                time("2023-03-14T00:00:00Z"),
+                &new_york,
+            )
+            .collect();
+        // the day of the spring forward transition lasts 23 hours.
+        let expected = vec![time("2023-03-12T05:00:00Z"), time("2023-03-13T04:00:00Z")];
+        assert_eq!(days, expected);
+        // and the one of the fall back transition 25 hours.
+        let day = time("2023-11-05T04:00:00Z");
+        assert_eq!(
+            Interval::OneDay.floor_aligned(&time("2023-11-06T04:30:00Z"), &new_york),
+            day
+        );
+        assert_eq!(
+            Interval::OneDay.next_aligned(day, &new_york),
+            time("2023-11-06T05:00:00Z")
+        );
+        assert_eq!(
+            Interval::OneMonth.next_aligned(time("2023-03-01T05:00:00Z"), &new_york),
+            time("2023-04-01T04:00:00Z")
+        );
+
+        // clocks jumped from midnight to 01:00, the day starts at the transition.
+        let sao_paulo = Alignment::from(TimeZone::Iana(chrono_tz::America::Sao_Paulo));
+        let day = time("2018-11-04T03:00:00Z");
+        assert_eq!(
+            Interval::OneDay.floor_aligned(&time("2018-11-04T12:00:00Z"), &sao_paulo),
+            day
+        );
+        assert_eq!(
+            Interval::OneDay.next_aligned(day, &sao_paulo),
+            time("2018-11-05T02:00:00Z")
+        );
+        assert_eq!(
+            Interval::OneDay.next_aligned(time("2018-11-03T03:00:00Z"), &sao_paulo),
+            day
+        );
+
+        // Samoa skipped 2011-12-30 entirely, the 29th is followed by the 31st.
+        let apia = Alignment::from(TimeZone::Iana(chrono_tz::Pacific::Apia));
+        let day = time("2011-12-30T10:00:00Z");
+        assert_eq!(
+            Interval::OneDay.next_aligned(time("2011-12-29T10:00:00Z"), &apia),
+            day
+        );
+        assert_eq!(Interval::OneDay.floor_aligned(&day, &apia), day);
+    }
+}
@@ -1,4 +1,5 @@
 use core::fmt;
+use core::hash::Hash;
 
-pub trait Asset: Eq + Clone + fmt::Debug + fmt::Display {}
-impl<T> Asset for T where T: Eq + Clone + fmt::Debug + fmt::Display {}
+pub trait Asset: Eq + Hash + Clone + fmt::Debug + fmt::Display {}
+impl<T> Asset for T where T: Eq + Hash + Clone + fmt::Debug + fmt::Display {}
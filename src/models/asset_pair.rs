@@ -1,7 +1,8 @@
 use super::Asset;
 use core::fmt;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssetPair<A: Asset> {
     pub base_asset: A,
     pub quote_asset: A,
@@ -1,27 +1,247 @@
-use super::{Asset, AssetPair, Interval, OrderFilled, Price, Quantity, Time, Volume};
+use super::{
+    Asset, AssetPair, Interval, OrderFilled, Price, Quantity, RoundingMode, Side, Time, Volume,
+};
+use crate::{Error, Result};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CandlestickData {
     pub open: Price,
     pub high: Price,
     pub low: Price,
     pub close: Price,
+    /// sum of the base quantities.
     pub volume: Volume,
+    /// sum of price * quantity, in the quote asset, unknown when read from a file without it.
+    pub quote_volume: Option<Volume>,
+    /// number of filled orders.
+    pub trade_count: u64,
+    /// volume weighted average price, at the largest scale of the OHLC prices.
+    /// Without a quote volume, the VWAPs of combined data are weighted by their volumes.
+    pub vwap: Price,
+    /// ids of the first and last filled orders, when known.
+    /// Orders are ranked by trade time, then by trade id, as for the open and close.
+    pub first_trade_id: Option<u64>,
+    pub last_trade_id: Option<u64>,
+    /// execution time of the first and last filled orders, when known.
+    pub first_trade_time: Option<Time>,
+    pub last_trade_time: Option<Time>,
+    /// base quantity of the trades whose taker was the buyer, unknown like the quote volume.
+    pub taker_buy_volume: Option<Volume>,
+    pub taker_buy_quote_volume: Option<Volume>,
+    /// base quantity of the trades whose taker was the seller.
+    pub taker_sell_volume: Option<Volume>,
+    pub taker_sell_quote_volume: Option<Volume>,
 }
 
 impl CandlestickData {
-    pub fn new_with_price_and_quantity(price: Price, quantity: Quantity) -> Self {
-        Self {
+    pub fn new_with_price_and_quantity(price: Price, quantity: Quantity) -> Result<Self> {
+        let quote_volume = Volume::new_quote(&price, &quantity)?;
+        Ok(Self {
             open: price,
             high: price,
             low: price,
             close: price,
             volume: Volume::new(quantity),
+            quote_volume: Some(quote_volume),
+            trade_count: 1,
+            vwap: price,
+            first_trade_id: None,
+            last_trade_id: None,
+            first_trade_time: None,
+            last_trade_time: None,
+            taker_buy_volume: Some(Volume::zero(quantity.scale)),
+            taker_buy_quote_volume: Some(Volume::zero(quote_volume.scale)),
+            taker_sell_volume: Some(Volume::zero(quantity.scale)),
+            taker_sell_quote_volume: Some(Volume::zero(quote_volume.scale)),
+        })
+    }
+
+    pub fn new_with_order_filled<A: Asset>(order_filled: &OrderFilled<A>) -> Result<Self> {
+        let mut data =
+            Self::new_with_price_and_quantity(order_filled.price, order_filled.quantity)?;
+        data.first_trade_id = order_filled.trade_id;
+        data.last_trade_id = order_filled.trade_id;
+        data.first_trade_time = Some(order_filled.time);
+        data.last_trade_time = Some(order_filled.time);
+        if let Some(side) = order_filled.side {
+            data.add_taker_volume(side, order_filled.price, order_filled.quantity)?;
+        }
+        Ok(data)
+    }
+
+    /// the data is left untouched on error.
+    pub fn add_price_and_quantity(&mut self, price: Price, quantity: Quantity) -> Result<()> {
+        let quote = Volume::new_quote(&price, &quantity)?;
+        let volume = self.volume.checked_add(&Volume::new(quantity))?;
+        let quote_volume = checked_sum(self.quote_volume, Some(quote))?;
+        let trade_count = self.trade_count.checked_add(1).ok_or(Error::Overflow)?;
+        let mut updated = Self {
+            high: self.high.max(price),
+            low: self.low.min(price),
+            close: price,
+            volume,
+            quote_volume,
+            trade_count,
+            ..self.clone()
+        };
+        updated.vwap = updated.compute_vwap(&self.quote_estimate()?.checked_add(&quote)?)?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// the order opens or closes the data by trade time, then by trade id, as in `merge`.
+    /// The data is left untouched on error.
+    pub fn add_order_filled<A: Asset>(&mut self, order_filled: &OrderFilled<A>) -> Result<()> {
+        *self = self.merge(&Self::new_with_order_filled(order_filled)?)?;
+        Ok(())
+    }
+
+    fn add_taker_volume(&mut self, side: Side, price: Price, quantity: Quantity) -> Result<()> {
+        let (volume, quote_volume) = match side {
+            Side::Buy => (&mut self.taker_buy_volume, &mut self.taker_buy_quote_volume),
+            Side::Sell => (
+                &mut self.taker_sell_volume,
+                &mut self.taker_sell_quote_volume,
+            ),
+        };
+        let new_volume = checked_sum(*volume, Some(Volume::new(quantity)))?;
+        let new_quote_volume =
+            checked_sum(*quote_volume, Some(Volume::new_quote(&price, &quantity)?))?;
+        *volume = new_volume;
+        *quote_volume = new_quote_volume;
+        Ok(())
+    }
+
+    /// combined data of the same bucket, e.g. from two shards.
+    /// The open, first trade id and time come from the data whose first trade is earlier,
+    /// the close, last trade id and time from the data whose last trade is later,
+    /// by trade time, then by trade id, then in argument order.
+    pub fn merge(&self, other: &CandlestickData) -> Result<Self> {
+        let first = if other.opens_before(self) {
+            other
+        } else {
+            self
+        };
+        let last = if self.closes_after(other) {
+            self
+        } else {
+            other
+        };
+        let mut merged = Self {
+            open: first.open,
+            high: self.high.max(other.high),
+            low: self.low.min(other.low),
+            close: last.close,
+            volume: self.volume.checked_add(&other.volume)?,
+            quote_volume: checked_sum(self.quote_volume, other.quote_volume)?,
+            trade_count: self
+                .trade_count
+                .checked_add(other.trade_count)
+                .ok_or(Error::Overflow)?,
+            vwap: self.vwap,
+            first_trade_id: first.first_trade_id,
+            last_trade_id: last.last_trade_id,
+            first_trade_time: first.first_trade_time,
+            last_trade_time: last.last_trade_time,
+            taker_buy_volume: checked_sum(self.taker_buy_volume, other.taker_buy_volume)?,
+            taker_buy_quote_volume: checked_sum(
+                self.taker_buy_quote_volume,
+                other.taker_buy_quote_volume,
+            )?,
+            taker_sell_volume: checked_sum(self.taker_sell_volume, other.taker_sell_volume)?,
+            taker_sell_quote_volume: checked_sum(
+                self.taker_sell_quote_volume,
+                other.taker_sell_quote_volume,
+            )?,
+        };
+        let quote_volume = self
+            .quote_estimate()?
+            .checked_add(&other.quote_estimate()?)?;
+        merged.vwap = merged.compute_vwap(&quote_volume)?;
+        Ok(merged)
+    }
+
+    /// strictly, ties keep the argument order.
+    fn opens_before(&self, other: &CandlestickData) -> bool {
+        match (self.first_trade_time, other.first_trade_time) {
+            (Some(lhs), Some(rhs)) if lhs != rhs => lhs < rhs,
+            _ => matches!(
+                (self.first_trade_id, other.first_trade_id),
+                (Some(lhs), Some(rhs)) if lhs < rhs
+            ),
+        }
+    }
+
+    /// strictly, ties keep the argument order.
+    fn closes_after(&self, other: &CandlestickData) -> bool {
+        match (self.last_trade_time, other.last_trade_time) {
+            (Some(lhs), Some(rhs)) if lhs != rhs => lhs > rhs,
+            _ => matches!(
+                (self.last_trade_id, other.last_trade_id),
+                (Some(lhs), Some(rhs)) if lhs > rhs
+            ),
+        }
+    }
+
+    /// the quote volume, or the VWAP times the volume when it is unknown.
+    fn quote_estimate(&self) -> Result<Volume> {
+        match self.quote_volume {
+            Some(quote_volume) => Ok(quote_volume),
+            None => Volume::new_quote(
+                &self.vwap,
+                &Quantity::new(self.volume.num, self.volume.scale),
+            ),
+        }
+    }
+
+    /// `quote_volume` of all the trades, estimated or not.
+    fn compute_vwap(&self, quote_volume: &Volume) -> Result<Price> {
+        if self.volume.is_zero() {
+            return Ok(self.close);
+        }
+        let scale = [self.open, self.high, self.low, self.close]
+            .iter()
+            .map(|price| price.scale)
+            .max()
+            .unwrap_or_default();
+        let vwap = quote_volume.checked_div(&self.volume, scale, RoundingMode::HalfUp)?;
+        Ok(Price::new(vwap.num, vwap.scale))
+    }
+
+    /// flat data at the close price with zero volume.
+    pub fn carry_forward(&self) -> Self {
+        let zero = |volume: Option<Volume>| volume.map(|volume| Volume::zero(volume.scale));
+        Self {
+            open: self.close,
+            high: self.close,
+            low: self.close,
+            close: self.close,
+            volume: Volume::zero(self.volume.scale),
+            quote_volume: zero(self.quote_volume),
+            trade_count: 0,
+            vwap: self.close,
+            first_trade_id: None,
+            last_trade_id: None,
+            first_trade_time: None,
+            last_trade_time: None,
+            taker_buy_volume: zero(self.taker_buy_volume),
+            taker_buy_quote_volume: zero(self.taker_buy_quote_volume),
+            taker_sell_volume: zero(self.taker_sell_volume),
+            taker_sell_quote_volume: zero(self.taker_sell_quote_volume),
         }
     }
 }
 
+/// sum of two volumes, unknown unless both are known.
+fn checked_sum(lhs: Option<Volume>, rhs: Option<Volume>) -> Result<Option<Volume>> {
+    lhs.zip(rhs)
+        .map(|(lhs, rhs)| lhs.checked_add(&rhs))
+        .transpose()
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Candlestick<A: Asset> {
     pub asset_pair: AssetPair<A>,
     pub open_time: Time,
@@ -58,21 +278,234 @@ impl<A: Asset> Candlestick<A> {
         self.data = data;
     }
 
-    pub fn add_order_filled(&mut self, order_filled: OrderFilled<A>) {
-        if let Some(data) = &mut self.data {
-            if data.high < order_filled.price {
-                data.high = order_filled.price;
+    /// the candlestick is left untouched on error.
+    pub fn add_order_filled(&mut self, order_filled: &OrderFilled<A>) -> Result<()> {
+        match &mut self.data {
+            Some(data) => data.add_order_filled(order_filled),
+            None => {
+                self.data = Some(CandlestickData::new_with_order_filled(order_filled)?);
+                Ok(())
             }
-            if order_filled.price < data.low {
-                data.low = order_filled.price;
-            }
-            data.close = order_filled.price;
-            data.volume.add_quantity(&order_filled.quantity);
-        } else {
-            self.data = Some(CandlestickData::new_with_price_and_quantity(
-                order_filled.price,
-                order_filled.quantity,
+        }
+    }
+
+    /// combined candlestick of the same asset pair, interval and open time.
+    pub fn merge(&self, other: &Candlestick<A>) -> Result<Self> {
+        if self.asset_pair != other.asset_pair {
+            return Err(Error::asset_pair_mismatch(
+                &self.asset_pair,
+                &other.asset_pair,
             ));
         }
+        if self.interval != other.interval {
+            return Err(Error::IntervalMismatch {
+                expected: self.interval,
+                actual: other.interval,
+            });
+        }
+        if self.open_time != other.open_time {
+            return Err(Error::OpenTimeMismatch {
+                expected: self.open_time,
+                actual: other.open_time,
+            });
+        }
+        let data = match (&self.data, &other.data) {
+            (Some(data), Some(other_data)) => Some(data.merge(other_data)?),
+            (data, other_data) => data.clone().or_else(|| other_data.clone()),
+        };
+        Ok(Self::new_with_data(
+            self.asset_pair.clone(),
+            self.open_time,
+            self.interval,
+            data,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{asset_pair, order_filled};
+
+    #[test]
+    fn test_quote_volume() {
+        let mut data =
+            CandlestickData::new_with_price_and_quantity(Price::new(1505, 2), Quantity::new(3, 1))
+                .unwrap();
+        data.add_price_and_quantity(Price::new(15, 0), Quantity::new(125, 3))
+            .unwrap();
+        // 15.05 * 0.3 + 15 * 0.125 = 4.515 + 1.875, at the price scale plus the quantity scale
+        let quote_volume = data.quote_volume.unwrap();
+        assert_eq!((quote_volume.num, quote_volume.scale), (6390, 3));
+        assert_eq!(data.volume, Volume { num: 425, scale: 3 });
+        assert_eq!(data.high, Price::new(1505, 2));
+        assert_eq!(data.low, Price::new(15, 0));
+
+        let result = data.add_price_and_quantity(Price::new(u64::MAX, 0), Quantity::new(2, 0));
+        assert!(matches!(result, Err(Error::Overflow)));
+        assert_eq!(data.close, Price::new(15, 0));
+        assert_eq!(data.volume, Volume { num: 425, scale: 3 });
+    }
+
+    #[test]
+    fn test_trade_statistics() {
+        let order_filled = |price: u64, quantity: u64, trade_id: Option<u64>| OrderFilled {
+            trade_id,
+            ..OrderFilled::new(
+                asset_pair(),
+                Price::new(price, 1),
+                Quantity::new(quantity, 0),
+                Time::new(0),
+            )
+        };
+        let mut data = CandlestickData::new_with_order_filled(&order_filled(100, 1, None)).unwrap();
+        data.add_order_filled(&order_filled(130, 2, Some(7)))
+            .unwrap();
+        data.add_order_filled(&order_filled(110, 3, Some(8)))
+            .unwrap();
+        assert_eq!(data.trade_count, 3);
+        // (10 * 1 + 13 * 2 + 11 * 3) / 6 = 11.5
+        assert_eq!(data.vwap, Price::new(115, 1));
+        // the first fill carries no trade id.
+        assert_eq!(data.first_trade_id, None);
+        assert_eq!(data.last_trade_id, Some(8));
+    }
+
+    #[test]
+    fn test_taker_volume() {
+        let order_filled = |price: u64, quantity: u64, side: Option<Side>| OrderFilled {
+            side,
+            ..OrderFilled::new(
+                asset_pair(),
+                Price::new(price, 0),
+                Quantity::new(quantity, 1),
+                Time::new(0),
+            )
+        };
+        let mut data =
+            CandlestickData::new_with_order_filled(&order_filled(10, 5, Some(Side::Buy))).unwrap();
+        data.add_order_filled(&order_filled(20, 15, Some(Side::Sell)))
+            .unwrap();
+        data.add_order_filled(&order_filled(30, 10, Some(Side::Buy)))
+            .unwrap();
+        data.add_order_filled(&order_filled(40, 10, None)).unwrap();
+        assert_eq!(data.taker_buy_volume, Some(Volume { num: 15, scale: 1 }));
+        assert_eq!(
+            data.taker_buy_quote_volume,
+            Some(Volume { num: 35, scale: 0 })
+        );
+        assert_eq!(data.taker_sell_volume, Some(Volume { num: 15, scale: 1 }));
+        assert_eq!(
+            data.taker_sell_quote_volume,
+            Some(Volume { num: 30, scale: 0 })
+        );
+        assert_eq!(data.volume, Volume { num: 4, scale: 0 });
+
+        let flat = data.carry_forward();
+        assert!(flat.taker_buy_volume.unwrap().is_zero());
+        assert!(flat.taker_sell_quote_volume.unwrap().is_zero());
+    }
+
+    #[test]
+    fn test_merge() {
+        let trade = |price: u64, time: i64, trade_id: u64| {
+            order_filled(price, time)
+                .with_trade_id(trade_id)
+                .with_side(Side::Buy)
+        };
+        let shard = |trades: &[(u64, i64, u64)]| {
+            let mut candlestick = Candlestick::new(asset_pair(), Time::new(0), Interval::OneMinute);
+            for (price, time, trade_id) in trades {
+                candlestick
+                    .add_order_filled(&trade(*price, *time, *trade_id))
+                    .unwrap();
+            }
+            candlestick
+        };
+        let lhs = shard(&[(12, 5, 2), (8, 40, 5)]);
+        let rhs = shard(&[(10, 1, 1), (20, 30, 4), (14, 50, 6)]);
+        for merged in [lhs.merge(&rhs).unwrap(), rhs.merge(&lhs).unwrap()] {
+            let data = merged.data.unwrap();
+            let ohlc = (data.open.num, data.high.num, data.low.num, data.close.num);
+            assert_eq!(ohlc, (10, 20, 8, 14));
+            assert_eq!((data.volume.num, data.trade_count), (5, 5));
+            assert_eq!(data.taker_buy_volume.unwrap().num, 5);
+            // 64 / 5 at the scale of the prices
+            assert_eq!(data.vwap, Price::new(13, 0));
+            assert_eq!(
+                (data.first_trade_id, data.last_trade_id),
+                (Some(1), Some(6))
+            );
+            assert_eq!(data.first_trade_time, Some(Time::new(1)));
+            assert_eq!(data.last_trade_time, Some(Time::new(50)));
+        }
+
+        let empty = Candlestick::new(asset_pair(), Time::new(0), Interval::OneMinute);
+        assert_eq!(lhs.merge(&empty).unwrap().data.unwrap().close.num, 8);
+        assert!(empty.merge(&empty).unwrap().data.is_none());
+
+        let mut other = empty.clone();
+        other.open_time = Time::new(60);
+        assert!(matches!(
+            lhs.merge(&other),
+            Err(Error::OpenTimeMismatch { .. })
+        ));
+        let mut other = empty.clone();
+        other.interval = Interval::FiveMinutes;
+        assert!(matches!(
+            lhs.merge(&other),
+            Err(Error::IntervalMismatch { .. })
+        ));
+        let mut other = empty;
+        other.asset_pair.base_asset = "ETH".to_owned();
+        assert!(matches!(
+            lhs.merge(&other),
+            Err(Error::AssetPairMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge_matches_sequential() {
+        // trades arrive out of order, as they do across shards, and share trade times.
+        let trades: Vec<OrderFilled<String>> = [
+            (10, 2, 5),
+            (9, 4, 9),
+            (12, 1, 3),
+            (8, 4, 7),
+            (11, 3, 2),
+            (13, 1, 4),
+        ]
+        .into_iter()
+        .map(|(price, time, trade_id)| order_filled(price, time).with_trade_id(trade_id))
+        .collect();
+        let apply = |trades: &[OrderFilled<String>]| {
+            let mut data = CandlestickData::new_with_order_filled(&trades[0]).unwrap();
+            for trade in &trades[1..] {
+                data.add_order_filled(trade).unwrap();
+            }
+            data
+        };
+        let price_of = |trade_id: Option<u64>| {
+            let trade = trades.iter().find(|trade| trade.trade_id == trade_id);
+            trade.unwrap().price.num
+        };
+        let sequential = apply(&trades);
+        let merged = apply(&trades[..3]).merge(&apply(&trades[3..])).unwrap();
+        let reversed = apply(&trades[3..]).merge(&apply(&trades[..3])).unwrap();
+        for data in [&sequential, &merged, &reversed] {
+            assert_eq!(
+                (data.first_trade_id, data.last_trade_id),
+                (Some(3), Some(9))
+            );
+            assert_eq!(data.open.num, price_of(data.first_trade_id));
+            assert_eq!(data.close.num, price_of(data.last_trade_id));
+            let ohlc = (data.open.num, data.high.num, data.low.num, data.close.num);
+            assert_eq!(ohlc, (12, 13, 8, 9));
+            assert_eq!((data.volume.num, data.trade_count), (6, 6));
+            assert_eq!(
+                (data.first_trade_time, data.last_trade_time),
+                (Some(Time::new(1)), Some(Time::new(4)))
+            );
+        }
     }
 }
@@ -0,0 +1,551 @@
+//! Arithmetic shared by the `{ num, scale }` fixed point types, whose value is `num * 10^-scale`.
+use crate::{Error, Result};
+use std::cmp::Ordering;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// toward zero.
+    Down,
+    /// away from zero.
+    Up,
+    /// to the nearest, ties away from zero.
+    HalfUp,
+    /// to the nearest, ties to the even neighbor.
+    HalfEven,
+}
+
+fn pow10(exp: u32) -> Option<u128> {
+    10u128.checked_pow(exp)
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| Error::Overflow)
+}
+
+/// `None` means the result does not even fit in u128, so it is larger than any u64.
+fn upscale(num: u64, exp: u32) -> Option<u128> {
+    if num == 0 {
+        return Some(0);
+    }
+    pow10(exp).and_then(|factor| (num as u128).checked_mul(factor))
+}
+
+/// `None` divisor stands for a divisor larger than any u128.
+fn div_round(n: u128, d: Option<u128>, rounding: RoundingMode) -> u128 {
+    let d = match d {
+        Some(d) => d,
+        None => return (rounding == RoundingMode::Up && n > 0) as u128,
+    };
+    let (q, r) = (n / d, n % d);
+    let round_up = match rounding {
+        RoundingMode::Down => false,
+        RoundingMode::Up => r > 0,
+        RoundingMode::HalfUp => r > 0 && r >= d - r,
+        RoundingMode::HalfEven => r > 0 && (r > d - r || (r == d - r && q % 2 == 1)),
+    };
+    if round_up {
+        q + 1
+    } else {
+        q
+    }
+}
+
+pub(crate) fn cmp(lhs: (u64, u32), rhs: (u64, u32)) -> Ordering {
+    match lhs.1.cmp(&rhs.1) {
+        Ordering::Equal => lhs.0.cmp(&rhs.0),
+        Ordering::Less => match upscale(lhs.0, rhs.1 - lhs.1) {
+            Some(num) => num.cmp(&(rhs.0 as u128)),
+            None => Ordering::Greater,
+        },
+        Ordering::Greater => cmp(rhs, lhs).reverse(),
+    }
+}
+
+pub(crate) fn rescale(value: (u64, u32), scale: u32, rounding: RoundingMode) -> Result<u64> {
+    let (num, current) = value;
+    if scale >= current {
+        upscale(num, scale - current)
+            .ok_or(Error::Overflow)
+            .and_then(to_u64)
+    } else {
+        to_u64(div_round(num as u128, pow10(current - scale), rounding))
+    }
+}
+
+pub(crate) fn add(lhs: (u64, u32), rhs: (u64, u32)) -> Result<(u64, u32)> {
+    let scale = lhs.1.max(rhs.1);
+    let lhs = rescale(lhs, scale, RoundingMode::Down)?;
+    let rhs = rescale(rhs, scale, RoundingMode::Down)?;
+    let num = lhs.checked_add(rhs).ok_or(Error::Overflow)?;
+    Ok((num, scale))
+}
+
+pub(crate) fn sub(lhs: (u64, u32), rhs: (u64, u32)) -> Result<(u64, u32)> {
+    let scale = lhs.1.max(rhs.1);
+    let lhs = rescale(lhs, scale, RoundingMode::Down)?;
+    let rhs = rescale(rhs, scale, RoundingMode::Down)?;
+    let num = lhs.checked_sub(rhs).ok_or(Error::Underflow)?;
+    Ok((num, scale))
+}
+
+/// exact product, trailing zeros are dropped only when the product does not fit otherwise.
+pub(crate) fn mul(lhs: (u64, u32), rhs: (u64, u32)) -> Result<(u64, u32)> {
+    let mut num = lhs.0 as u128 * rhs.0 as u128;
+    let mut scale = lhs.1.checked_add(rhs.1).ok_or(Error::Overflow)?;
+    while num > u64::MAX as u128 && scale > 0 {
+        let (quotient, remainder) = (num / 10, num % 10);
+        if remainder != 0 {
+            break;
+        }
+        num = quotient;
+        scale -= 1;
+    }
+    Ok((to_u64(num)?, scale))
+}
+
+pub(crate) fn div(
+    lhs: (u64, u32),
+    rhs: (u64, u32),
+    scale: u32,
+    rounding: RoundingMode,
+) -> Result<(u64, u32)> {
+    if rhs.0 == 0 {
+        return Err(Error::DivisionByZero);
+    }
+    // lhs / rhs * 10^scale = lhs.num * 10^(scale + rhs.scale - lhs.scale) / rhs.num
+    let exp = scale as i64 + rhs.1 as i64 - lhs.1 as i64;
+    let num = if exp >= 0 {
+        let n = upscale(lhs.0, exp as u32).ok_or(Error::Overflow)?;
+        div_round(n, Some(rhs.0 as u128), rounding)
+    } else {
+        let d = upscale(rhs.0, exp.unsigned_abs() as u32);
+        div_round(lhs.0 as u128, d, rounding)
+    };
+    Ok((to_u64(num)?, scale))
+}
+
+/// what happens to the trailing fractional zeros of a parsed decimal.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TrailingZeros {
+    /// every written digit counts, `"1.50"` has scale 2.
+    Keep,
+    /// `"1.50"` has scale 1 and `"2.00"` scale 0.
+    Trim,
+}
+
+/// how decimal strings are parsed into `Price`, `Quantity` and `Volume`.
+///
+/// the default keeps the written scale, which is also what `FromStr` does.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DecimalOptions {
+    /// scale of the result, `None` for the written scale.
+    pub scale: Option<u32>,
+    /// applied to the digits beyond `scale`.
+    pub rounding: RoundingMode,
+    /// ignored when `scale` is set.
+    pub trailing_zeros: TrailingZeros,
+}
+
+impl Default for DecimalOptions {
+    fn default() -> Self {
+        Self {
+            scale: None,
+            rounding: RoundingMode::HalfEven,
+            trailing_zeros: TrailingZeros::Keep,
+        }
+    }
+}
+
+impl DecimalOptions {
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn with_rounding(mut self, rounding: RoundingMode) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    pub fn with_trailing_zeros(mut self, trailing_zeros: TrailingZeros) -> Self {
+        self.trailing_zeros = trailing_zeros;
+        self
+    }
+}
+
+/// decimal with exactly `scale` fractional digits.
+pub(crate) fn format(value: (u64, u32)) -> String {
+    let (num, scale) = value;
+    let digits = num.to_string();
+    let scale = scale as usize;
+    if scale == 0 {
+        digits
+    } else if digits.len() > scale {
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        format!("{}.{}", integer, fraction)
+    } else {
+        format!("0.{}{}", "0".repeat(scale - digits.len()), digits)
+    }
+}
+
+/// whether the dropped digits round the kept ones, ending in `last`, up.
+fn round_digits(last: u64, dropped: &[u8], rounding: RoundingMode) -> bool {
+    let (first, rest) = match dropped.split_first() {
+        Some((first, rest)) => (*first, rest),
+        None => return false,
+    };
+    let rest_nonzero = rest.iter().any(|digit| *digit != b'0');
+    match rounding {
+        RoundingMode::Down => false,
+        RoundingMode::Up => first != b'0' || rest_nonzero,
+        RoundingMode::HalfUp => first >= b'5',
+        RoundingMode::HalfEven => {
+            first > b'5' || (first == b'5' && (rest_nonzero || last % 2 == 1))
+        }
+    }
+}
+
+/// plain decimal like `"0.00012340"`, an optional `+` sign aside no exponent, separator or space.
+pub(crate) fn parse(value: &str, options: &DecimalOptions) -> Result<(u64, u32)> {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (unsigned, ""),
+    };
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) || unsigned.ends_with('.')
+    {
+        return Err(Error::InvalidDecimal {
+            value: value.to_owned(),
+        });
+    }
+    if negative {
+        return Err(Error::NegativeDecimal {
+            value: value.to_owned(),
+        });
+    }
+
+    let fraction = match (options.scale, options.trailing_zeros) {
+        (None, TrailingZeros::Trim) => fraction.trim_end_matches('0'),
+        _ => fraction,
+    };
+    let scale = match options.scale {
+        Some(scale) => scale,
+        None => u32::try_from(fraction.len()).map_err(|_| Error::Overflow)?,
+    };
+    let (kept, dropped) = fraction
+        .as_bytes()
+        .split_at(fraction.len().min(scale as usize));
+    let mut num: u64 = 0;
+    for digit in integer.bytes().chain(kept.iter().copied()) {
+        num = num
+            .checked_mul(10)
+            .and_then(|num| num.checked_add(u64::from(digit - b'0')))
+            .ok_or(Error::Overflow)?;
+    }
+    // kept digits shorter than the requested scale are padded with zeros.
+    let padding = scale - kept.len() as u32;
+    num = rescale((num, 0), padding, RoundingMode::Down)?;
+    if round_digits(num, dropped, options.rounding) {
+        num = num.checked_add(1).ok_or(Error::Overflow)?;
+    }
+    Ok((num, scale))
+}
+
+/// implements fixed point comparison, arithmetic and decimal strings for a `{ num: u64, scale: u32 }` struct.
+macro_rules! impl_fixed_point {
+    ($name:ident) => {
+        impl $name {
+            fn parts(&self) -> (u64, u32) {
+                (self.num, self.scale)
+            }
+
+            fn from_parts((num, scale): (u64, u32)) -> Self {
+                Self { num, scale }
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.num == 0
+            }
+
+            /// same value at the given scale, rounded when the scale decreases.
+            pub fn rescale(
+                &self,
+                scale: u32,
+                rounding: $crate::models::RoundingMode,
+            ) -> $crate::Result<Self> {
+                $crate::models::fixed_point::rescale(self.parts(), scale, rounding)
+                    .map(|num| Self { num, scale })
+            }
+
+            /// sum at the larger scale of both operands.
+            pub fn checked_add(&self, other: &Self) -> $crate::Result<Self> {
+                $crate::models::fixed_point::add(self.parts(), other.parts()).map(Self::from_parts)
+            }
+
+            /// difference at the larger scale of both operands, `Error::Underflow` if negative.
+            pub fn checked_sub(&self, other: &Self) -> $crate::Result<Self> {
+                $crate::models::fixed_point::sub(self.parts(), other.parts()).map(Self::from_parts)
+            }
+
+            /// exact product at the sum of both scales.
+            pub fn checked_mul(&self, other: &Self) -> $crate::Result<Self> {
+                $crate::models::fixed_point::mul(self.parts(), other.parts()).map(Self::from_parts)
+            }
+
+            /// quotient at the given scale.
+            pub fn checked_div(
+                &self,
+                other: &Self,
+                scale: u32,
+                rounding: $crate::models::RoundingMode,
+            ) -> $crate::Result<Self> {
+                $crate::models::fixed_point::div(self.parts(), other.parts(), scale, rounding)
+                    .map(Self::from_parts)
+            }
+
+            /// parses a decimal string like `"0.00012340"`, see `DecimalOptions`.
+            pub fn parse_with(
+                value: &str,
+                options: &$crate::models::DecimalOptions,
+            ) -> $crate::Result<Self> {
+                $crate::models::fixed_point::parse(value, options).map(Self::from_parts)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            /// decimal with exactly `scale` fractional digits, e.g. `"0.00012340"`.
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.pad(&$crate::models::fixed_point::format(self.parts()))
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = $crate::Error;
+
+            /// keeps the written scale, `"1.50"` has scale 2.
+            fn from_str(value: &str) -> $crate::Result<Self> {
+                Self::parse_with(value, &$crate::models::DecimalOptions::default())
+            }
+        }
+
+        /// lossless decimal string, e.g. `"0.00012340"`.
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                let value = String::deserialize(deserializer)?;
+                std::str::FromStr::from_str(&value).map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == std::cmp::Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                $crate::models::fixed_point::cmp(self.parts(), other.parts())
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+    };
+}
+
+pub(crate) use impl_fixed_point;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{Price, Quantity, Volume};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_cmp() {
+        assert!(Price::new(15, 1) < Price::new(2, 0));
+        assert_eq!(Price::new(15, 1), Price::new(150, 2));
+        assert_ne!(Price::new(15, 1), Price::new(15, 2));
+        assert!(Quantity::new(1, 0) > Quantity::new(u64::MAX, 30));
+        assert!(Quantity::new(1, 40) < Quantity::new(1, 0));
+        assert_eq!(Volume { num: 0, scale: 0 }, Volume { num: 0, scale: 50 });
+    }
+
+    #[test]
+    fn test_rescale() {
+        let tests = vec![
+            ((125, 2), 1, RoundingMode::Down, 12),
+            ((125, 2), 1, RoundingMode::Up, 13),
+            ((125, 2), 1, RoundingMode::HalfUp, 13),
+            ((125, 2), 1, RoundingMode::HalfEven, 12),
+            ((135, 2), 1, RoundingMode::HalfEven, 14),
+            ((124, 2), 1, RoundingMode::HalfUp, 12),
+            ((120, 2), 1, RoundingMode::Up, 12),
+            ((12, 1), 3, RoundingMode::Down, 1200),
+            ((0, 0), 40, RoundingMode::Down, 0),
+            ((5, 40), 0, RoundingMode::Up, 1),
+            ((5, 40), 0, RoundingMode::HalfUp, 0),
+        ];
+        for ((num, scale), new_scale, rounding, expected) in tests {
+            let price = Price::new(num, scale).rescale(new_scale, rounding).unwrap();
+            assert_eq!((price.num, price.scale), (expected, new_scale));
+        }
+        for (num, new_scale) in [(u64::MAX, 1), (1, 40)] {
+            assert!(matches!(
+                Price::new(num, 0).rescale(new_scale, RoundingMode::Down),
+                Err(Error::Overflow)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let lhs = Quantity::new(15, 1);
+        let rhs = Quantity::new(2, 0);
+        let sum = lhs.checked_add(&rhs).unwrap();
+        assert_eq!((sum.num, sum.scale), (35, 1));
+        let difference = rhs.checked_sub(&lhs).unwrap();
+        assert_eq!((difference.num, difference.scale), (5, 1));
+        assert!(matches!(lhs.checked_sub(&rhs), Err(Error::Underflow)));
+        let product = lhs.checked_mul(&rhs).unwrap();
+        assert_eq!((product.num, product.scale), (30, 1));
+        let quotient = rhs.checked_div(&lhs, 4, RoundingMode::HalfUp).unwrap();
+        assert_eq!((quotient.num, quotient.scale), (13333, 4));
+        let quotient = lhs.checked_div(&rhs, 0, RoundingMode::HalfUp).unwrap();
+        assert_eq!((quotient.num, quotient.scale), (1, 0));
+        assert!(matches!(
+            lhs.checked_div(&Quantity::new(0, 3), 2, RoundingMode::Down),
+            Err(Error::DivisionByZero)
+        ));
+        assert!(matches!(
+            Quantity::new(u64::MAX, 0).checked_add(&Quantity::new(1, 0)),
+            Err(Error::Overflow)
+        ));
+        let product = Quantity::new(10u64.pow(19), 2)
+            .checked_mul(&Quantity::new(10, 1))
+            .unwrap();
+        assert_eq!((product.num, product.scale), (10u64.pow(19), 2));
+    }
+
+    #[test]
+    fn test_decimal() {
+        let tests = vec![
+            ("0", (0, 0), "0"),
+            ("12", (12, 0), "12"),
+            ("+12", (12, 0), "12"),
+            ("0.00012340", (12340, 8), "0.00012340"),
+            ("15.05", (1505, 2), "15.05"),
+            ("007.10", (710, 2), "7.10"),
+            (
+                "18446744073709551615",
+                (u64::MAX, 0),
+                "18446744073709551615",
+            ),
+        ];
+        for (value, (num, scale), formatted) in tests {
+            let price = Price::from_str(value).unwrap();
+            assert_eq!((price.num, price.scale), (num, scale));
+            assert_eq!(price.to_string(), formatted);
+        }
+        assert_eq!(format!("{:>6}", Volume { num: 5, scale: 2 }), "  0.05");
+        for value in ["", ".", "1.", ".5", "1e3", "1.2.3", "1,5", " 1", "--1", "-"] {
+            assert!(matches!(
+                Quantity::from_str(value),
+                Err(Error::InvalidDecimal { .. })
+            ));
+        }
+        assert!(matches!(
+            Quantity::from_str("-0.5"),
+            Err(Error::NegativeDecimal { .. })
+        ));
+        assert!(matches!(
+            Quantity::from_str("18446744073709551616"),
+            Err(Error::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_decimal_options() {
+        let tests = vec![
+            ("1.2350", DecimalOptions::default(), (12350, 4)),
+            ("2.00", DecimalOptions::default(), (200, 2)),
+            (
+                "1.2350",
+                DecimalOptions::default().with_trailing_zeros(TrailingZeros::Trim),
+                (1235, 3),
+            ),
+            (
+                "2.00",
+                DecimalOptions::default().with_trailing_zeros(TrailingZeros::Trim),
+                (2, 0),
+            ),
+            ("1.2350", DecimalOptions::default().with_scale(2), (124, 2)),
+            ("1.2250", DecimalOptions::default().with_scale(2), (122, 2)),
+            ("1.2251", DecimalOptions::default().with_scale(2), (123, 2)),
+            (
+                "1.2201",
+                DecimalOptions::default()
+                    .with_scale(2)
+                    .with_rounding(RoundingMode::Up),
+                (123, 2),
+            ),
+            (
+                "1.2299",
+                DecimalOptions::default()
+                    .with_scale(2)
+                    .with_rounding(RoundingMode::Down),
+                (122, 2),
+            ),
+            ("1.5", DecimalOptions::default().with_scale(4), (15000, 4)),
+            // digits beyond u64 are fine as long as they are rounded away.
+            (
+                "0.123456789012345678901234",
+                DecimalOptions::default().with_scale(8),
+                (12345679, 8),
+            ),
+            (
+                "9.95",
+                DecimalOptions::default()
+                    .with_scale(0)
+                    .with_rounding(RoundingMode::HalfUp),
+                (10, 0),
+            ),
+        ];
+        for (value, options, expected) in tests {
+            let price = Price::parse_with(value, &options).unwrap();
+            assert_eq!(
+                (price.num, price.scale),
+                expected,
+                "{} {:?}",
+                value,
+                options
+            );
+        }
+        assert!(matches!(
+            Price::parse_with("1", &DecimalOptions::default().with_scale(20)),
+            Err(Error::Overflow)
+        ));
+    }
+}
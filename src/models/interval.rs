@@ -1,135 +1,368 @@
-use super::{Duration, Time};
+use super::{Alignment, Duration, Time};
 use crate::{Error, Result};
-use chrono::{Datelike, Months, NaiveDateTime, NaiveTime};
+use chrono::{Datelike, Days, Months, NaiveDate};
 use std::fmt;
 use std::ops::Deref;
 use std::str::FromStr;
 
-const ONE_MINUTE: &'static str = "1m";
-const FIVE_MINUTES: &'static str = "5m";
-const TEN_MINUTES: &'static str = "10m";
-const ONE_HOUR: &'static str = "1h";
-const TWO_HOURS: &'static str = "2h";
-const FOUR_HOURS: &'static str = "4h";
-const ONE_DAY: &'static str = "1d";
-const ONE_WEEK: &'static str = "1w";
-const ONE_MONTH: &'static str = "1M";
+const ONE_SECOND: &str = "1s";
+const ONE_MINUTE: &str = "1m";
+const THREE_MINUTES: &str = "3m";
+const FIVE_MINUTES: &str = "5m";
+const TEN_MINUTES: &str = "10m";
+const FIFTEEN_MINUTES: &str = "15m";
+const THIRTY_MINUTES: &str = "30m";
+const ONE_HOUR: &str = "1h";
+const TWO_HOURS: &str = "2h";
+const FOUR_HOURS: &str = "4h";
+const SIX_HOURS: &str = "6h";
+const EIGHT_HOURS: &str = "8h";
+const TWELVE_HOURS: &str = "12h";
+const ONE_DAY: &str = "1d";
+const THREE_DAYS: &str = "3d";
+const ONE_WEEK: &str = "1w";
+const ONE_MONTH: &str = "1M";
+const THREE_MONTHS: &str = "3M";
+const ONE_YEAR: &str = "1y";
+
+/// units of the `<n><unit>` grammar for fixed width intervals, largest first.
+const FIXED_UNITS: [(&str, Duration); 7] = [
+    ("d", Duration::from_days(1)),
+    ("h", Duration::from_hours(1)),
+    ("m", Duration::from_minutes(1)),
+    ("s", Duration::from_seconds(1)),
+    ("ms", Duration::from_millis(1)),
+    ("us", Duration::from_micros(1)),
+    ("ns", Duration::from_nanos(1)),
+];
+
+/// named intervals whose buckets are aligned to the epoch.
+const FIXED_INTERVALS: [Interval; 14] = [
+    Interval::OneSecond,
+    Interval::OneMinute,
+    Interval::ThreeMinutes,
+    Interval::FiveMinutes,
+    Interval::TenMinutes,
+    Interval::FifteenMinutes,
+    Interval::ThirtyMinutes,
+    Interval::OneHour,
+    Interval::TwoHours,
+    Interval::FourHours,
+    Interval::SixHours,
+    Interval::EightHours,
+    Interval::TwelveHours,
+    Interval::ThreeDays,
+];
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum Interval {
+    OneSecond,
     OneMinute,
+    ThreeMinutes,
     FiveMinutes,
     TenMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
     OneHour,
     TwoHours,
     FourHours,
+    SixHours,
+    EightHours,
+    TwelveHours,
     OneDay,
+    /// aligned to the epoch, like the other fixed intervals.
+    ThreeDays,
+    /// starts on the week start of the alignment, Sunday by default.
     OneWeek,
     OneMonth,
+    /// calendar quarter.
+    ThreeMonths,
+    OneYear,
+    /// fixed width buckets aligned to the epoch, see `Interval::custom`.
+    Custom(CustomInterval),
+}
+
+/// longest code of a custom width, `u64::MAX` nanoseconds.
+const CUSTOM_CODE_LEN: usize = 22;
+
+/// non-zero width without a named interval, keeping its code for `Interval::as_str`.
+#[derive(Eq, PartialEq, Copy, Clone)]
+pub struct CustomInterval {
+    duration: Duration,
+    code: [u8; CUSTOM_CODE_LEN],
+    code_len: u8,
+}
+
+impl CustomInterval {
+    fn new(duration: Duration) -> Self {
+        let nanos = duration.as_nanos();
+        // days are left out, a custom width must not read as the calendar `1d`.
+        let (unit, width) = FIXED_UNITS[1..]
+            .iter()
+            .find(|(_, width)| nanos.checked_rem(width.as_nanos()) == Some(0))
+            .expect("every duration is a multiple of a nanosecond");
+        let formatted = format!("{}{}", nanos / width.as_nanos(), unit);
+        let mut code = [0; CUSTOM_CODE_LEN];
+        code[..formatted.len()].copy_from_slice(formatted.as_bytes());
+        Self {
+            duration,
+            code,
+            code_len: formatted.len() as u8,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.code[..usize::from(self.code_len)]).expect("codes are ascii")
+    }
+}
+
+impl fmt::Debug for CustomInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CustomInterval")
+            .field(&self.duration)
+            .finish()
+    }
+}
+
+/// how the buckets of an interval are laid out.
+enum Span {
+    /// buckets of the duration aligned to the epoch.
+    Fixed(Duration),
+    Day,
+    Week,
+    /// calendar months, aligned to the start of the year.
+    Months(u32),
 }
 
 impl Interval {
+    /// fixed width buckets of `duration` aligned to the epoch, the named interval of that
+    /// width if there is one.
+    pub fn custom(duration: Duration) -> Result<Self> {
+        if duration.is_zero() {
+            return Err(Error::InvalidInterval {
+                value: "0ns".to_owned(),
+            });
+        }
+        Ok(Self::from_fixed(duration))
+    }
+
     pub fn as_str(&self) -> &str {
         self.as_ref()
     }
 
-    pub fn is_valid_time(&self, time: &Time) -> bool {
+    fn span(&self) -> Span {
         match self {
-            Self::OneMinute => {
-                let duration = Duration::from_minutes(1);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::FiveMinutes => {
-                let duration = Duration::from_minutes(5);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::TenMinutes => {
-                let duration = Duration::from_minutes(10);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::OneHour => {
-                let duration = Duration::from_hours(1);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::TwoHours => {
-                let duration = Duration::from_hours(2);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::FourHours => {
-                let duration = Duration::from_hours(4);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::OneDay => {
-                let duration = Duration::from_days(1);
-                time.value() % (duration.value() as i64) == 0
-            }
-            Self::OneWeek => {
-                let offset = Duration::from_days(3); // Thursday through Saturday
-                let duration = Duration::from_days(7);
-                (time.value() - offset.value() as i64) % (duration.value() as i64) == 0
-            }
-            Self::OneMonth => {
-                let dt = NaiveDateTime::from_timestamp(time.value(), 0);
-                dt.date().day() == 1 && dt.time() == NaiveTime::from_hms(0, 0, 0)
-            }
+            Self::OneSecond => Span::Fixed(Duration::from_seconds(1)),
+            Self::OneMinute => Span::Fixed(Duration::from_minutes(1)),
+            Self::ThreeMinutes => Span::Fixed(Duration::from_minutes(3)),
+            Self::FiveMinutes => Span::Fixed(Duration::from_minutes(5)),
+            Self::TenMinutes => Span::Fixed(Duration::from_minutes(10)),
+            Self::FifteenMinutes => Span::Fixed(Duration::from_minutes(15)),
+            Self::ThirtyMinutes => Span::Fixed(Duration::from_minutes(30)),
+            Self::OneHour => Span::Fixed(Duration::from_hours(1)),
+            Self::TwoHours => Span::Fixed(Duration::from_hours(2)),
+            Self::FourHours => Span::Fixed(Duration::from_hours(4)),
+            Self::SixHours => Span::Fixed(Duration::from_hours(6)),
+            Self::EightHours => Span::Fixed(Duration::from_hours(8)),
+            Self::TwelveHours => Span::Fixed(Duration::from_hours(12)),
+            Self::OneDay => Span::Day,
+            Self::ThreeDays => Span::Fixed(Duration::from_days(3)),
+            Self::OneWeek => Span::Week,
+            Self::OneMonth => Span::Months(1),
+            Self::ThreeMonths => Span::Months(3),
+            Self::OneYear => Span::Months(12),
+            Self::Custom(custom) => Span::Fixed(custom.duration),
+        }
+    }
+
+    /// named interval of that width if there is one, `Custom` otherwise.
+    fn from_fixed(duration: Duration) -> Self {
+        FIXED_INTERVALS
+            .into_iter()
+            .find(|interval| matches!(interval.span(), Span::Fixed(width) if width == duration))
+            .unwrap_or_else(|| Self::Custom(CustomInterval::new(duration)))
+    }
+
+    pub fn is_valid_time(&self, time: &Time) -> bool {
+        self.is_valid_time_aligned(time, &Alignment::default())
+    }
+
+    /// false for a time whose bucket starts out of range, it does not open a bucket.
+    pub fn is_valid_time_aligned(&self, time: &Time, alignment: &Alignment) -> bool {
+        self.checked_floor_aligned(time, alignment)
+            .is_ok_and(|floor| floor == *time)
+    }
+
+    /// truncate the time to the open time of the bucket containing it.
+    pub fn floor(&self, time: &Time) -> Time {
+        self.floor_aligned(time, &Alignment::default())
+    }
+
+    /// panics when the start of the bucket is out of range, see `checked_floor_aligned`.
+    pub fn floor_aligned(&self, time: &Time, alignment: &Alignment) -> Time {
+        self.checked_floor_aligned(time, alignment)
+            .expect("time out of range")
+    }
+
+    /// `Error::TimeOutOfRange` when the bucket starts before the representable range.
+    pub fn checked_floor_aligned(&self, time: &Time, alignment: &Alignment) -> Result<Time> {
+        match self.span() {
+            Span::Fixed(duration) => floor_to(time, duration),
+            _ => alignment.start_of_day(self.floor_date(alignment.local_date(time), alignment)),
         }
     }
 
+    /// open times of every bucket starting in `[from, to)`.
+    pub fn range(&self, from: Time, to: Time) -> IntervalRange {
+        self.range_aligned(from, to, &Alignment::default())
+    }
+
+    pub fn range_aligned(&self, from: Time, to: Time, alignment: &Alignment) -> IntervalRange {
+        let start = self.floor_aligned(&from, alignment);
+        let next = if start < from {
+            self.next_aligned(start, alignment)
+        } else {
+            start
+        };
+        IntervalRange {
+            interval: *self,
+            alignment: *alignment,
+            next,
+            end: to,
+        }
+    }
+
+    /// open time of the bucket following the one starting at `time`.
     pub fn next(&self, time: Time) -> Time {
-        match self {
-            Self::OneMinute => {
-                let duration = Duration::from_minutes(1);
-                time + duration
-            }
-            Self::FiveMinutes => {
-                let duration = Duration::from_minutes(5);
-                time + duration
-            }
-            Self::TenMinutes => {
-                let duration = Duration::from_minutes(10);
-                time + duration
-            }
-            Self::OneHour => {
-                let duration = Duration::from_hours(1);
-                time + duration
-            }
-            Self::TwoHours => {
-                let duration = Duration::from_hours(2);
-                time + duration
-            }
-            Self::FourHours => {
-                let duration = Duration::from_hours(4);
-                time + duration
+        self.next_aligned(time, &Alignment::default())
+    }
+
+    /// calendar intervals move on from the bucket containing `time`, wherever it starts.
+    ///
+    /// panics when the next bucket is out of range, see `checked_next_aligned`.
+    pub fn next_aligned(&self, time: Time, alignment: &Alignment) -> Time {
+        self.checked_next_aligned(time, alignment)
+            .expect("time out of range")
+    }
+
+    /// `Error::Overflow` or `Error::TimeOutOfRange` when the next bucket is out of range.
+    pub fn checked_next_aligned(&self, time: Time, alignment: &Alignment) -> Result<Time> {
+        let date = match self.span() {
+            Span::Fixed(duration) => return time.checked_add(duration),
+            Span::Day => alignment.local_date(&time).succ_opt(),
+            Span::Week => {
+                let date = self.floor_date(alignment.local_date(&time), alignment);
+                date.checked_add_days(Days::new(7))
             }
-            Self::OneDay => {
-                let duration = Duration::from_days(1);
-                time + duration
+            Span::Months(months) => {
+                let date = self.floor_date(alignment.local_date(&time), alignment);
+                date.checked_add_months(Months::new(months))
             }
-            Self::OneWeek => {
-                let duration = Duration::from_days(7);
-                time + duration
+        };
+        let date = date.ok_or_else(|| Error::TimeOutOfRange {
+            value: time.to_string(),
+        })?;
+        alignment.start_of_day(date)
+    }
+
+    /// whether every bucket of `other` is made of whole buckets of this interval.
+    ///
+    /// fixed widths tile calendar buckets when they divide a day and every UTC offset
+    /// of the time zone, see `Alignment::utc_offsets`.
+    pub fn is_divisor_of(&self, other: &Interval, alignment: &Alignment) -> bool {
+        let divides = |width: Duration, total: Duration| {
+            total.as_nanos().checked_rem(width.as_nanos()) == Some(0)
+        };
+        match (self.span(), other.span()) {
+            (Span::Fixed(width), Span::Fixed(total)) => divides(width, total),
+            (Span::Fixed(width), _) => {
+                divides(width, Duration::from_days(1))
+                    && alignment.utc_offsets().into_iter().all(|offset| {
+                        divides(
+                            width,
+                            Duration::from_seconds(u64::from(offset.unsigned_abs())),
+                        )
+                    })
             }
-            Self::OneMonth => {
-                let dt = NaiveDateTime::from_timestamp(time.value(), 0);
-                let next_dt = dt.date().checked_add_months(Months::new(1)).unwrap();
-                Time::new(next_dt.and_time(dt.time()).timestamp())
+            (Span::Day, Span::Day | Span::Week | Span::Months(_)) => true,
+            (Span::Week, Span::Week) => true,
+            (Span::Months(months), Span::Months(total)) => total % months == 0,
+            _ => false,
+        }
+    }
+
+    /// first date of the calendar bucket containing the date.
+    fn floor_date(&self, date: NaiveDate, alignment: &Alignment) -> NaiveDate {
+        match self.span() {
+            Span::Fixed(_) | Span::Day => date,
+            Span::Week => alignment.start_of_week(date),
+            Span::Months(months) => {
+                let month0 = date.month0() - date.month0() % months;
+                NaiveDate::from_ymd_opt(date.year(), month0 + 1, 1).unwrap()
             }
         }
     }
 }
 
+/// Iterator over bucket open times, see `Interval::range`.
+#[derive(Debug, Clone)]
+pub struct IntervalRange {
+    interval: Interval,
+    alignment: Alignment,
+    next: Time,
+    end: Time,
+}
+
+impl Iterator for IntervalRange {
+    type Item = Time;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+        let current = self.next;
+        self.next = self.interval.next_aligned(current, &self.alignment);
+        Some(current)
+    }
+}
+
+/// buckets of `duration` aligned to the epoch.
+fn floor_to(time: &Time, duration: Duration) -> Result<Time> {
+    let remainder = i128::from(time.as_nanos()).rem_euclid(i128::from(duration.as_nanos()));
+    let remainder = u64::try_from(remainder).expect("remainder is below the duration");
+    time.checked_sub(Duration::from_nanos(remainder))
+        .map_err(|_| Error::TimeOutOfRange {
+            value: time.to_string(),
+        })
+}
+
 impl AsRef<str> for Interval {
     fn as_ref(&self) -> &str {
         match self {
+            Self::OneSecond => ONE_SECOND,
             Self::OneMinute => ONE_MINUTE,
+            Self::ThreeMinutes => THREE_MINUTES,
             Self::FiveMinutes => FIVE_MINUTES,
             Self::TenMinutes => TEN_MINUTES,
+            Self::FifteenMinutes => FIFTEEN_MINUTES,
+            Self::ThirtyMinutes => THIRTY_MINUTES,
             Self::OneHour => ONE_HOUR,
             Self::TwoHours => TWO_HOURS,
             Self::FourHours => FOUR_HOURS,
+            Self::SixHours => SIX_HOURS,
+            Self::EightHours => EIGHT_HOURS,
+            Self::TwelveHours => TWELVE_HOURS,
             Self::OneDay => ONE_DAY,
+            Self::ThreeDays => THREE_DAYS,
             Self::OneWeek => ONE_WEEK,
             Self::OneMonth => ONE_MONTH,
+            Self::ThreeMonths => THREE_MONTHS,
+            Self::OneYear => ONE_YEAR,
+            Self::Custom(custom) => custom.as_str(),
         }
     }
 }
@@ -141,29 +374,41 @@ impl Deref for Interval {
     }
 }
 
-impl Into<String> for Interval {
-    fn into(self) -> String {
-        self.to_string()
+impl From<Interval> for String {
+    fn from(value: Interval) -> Self {
+        value.to_string()
     }
 }
 
 impl FromStr for Interval {
     type Err = Error;
 
+    /// `<n><unit>`, where unit is one of `ns`, `us`, `ms`, `s`, `m`, `h`, `d` for fixed widths,
+    /// except for the calendar intervals `1d`, `1w`, `1M`, `3M` and `1y`.
     fn from_str(value: &str) -> Result<Self> {
-        match value {
-            ONE_MINUTE => Ok(Self::OneMinute),
-            FIVE_MINUTES => Ok(Self::FiveMinutes),
-            TEN_MINUTES => Ok(Self::TenMinutes),
-            ONE_HOUR => Ok(Self::OneHour),
-            TWO_HOURS => Ok(Self::TwoHours),
-            FOUR_HOURS => Ok(Self::FourHours),
-            ONE_DAY => Ok(Self::OneDay),
-            ONE_WEEK => Ok(Self::OneWeek),
-            ONE_MONTH => Ok(Self::OneMonth),
-            _ => Err(Error::InvalidInterval {
-                value: value.to_owned(),
-            }),
+        let invalid = || Error::InvalidInterval {
+            value: value.to_owned(),
+        };
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (count, unit) = value.split_at(split);
+        let count: u64 = count.parse().map_err(|_| invalid())?;
+        match (count, unit) {
+            (0, _) => Err(invalid()),
+            (1, "d") => Ok(Self::OneDay),
+            (1, "w") => Ok(Self::OneWeek),
+            (1, "M") => Ok(Self::OneMonth),
+            (3, "M") => Ok(Self::ThreeMonths),
+            (1, "y") => Ok(Self::OneYear),
+            _ => {
+                let (_, width) = FIXED_UNITS
+                    .iter()
+                    .find(|(code, _)| *code == unit)
+                    .ok_or_else(invalid)?;
+                let duration = width.checked_mul(count).map_err(|_| invalid())?;
+                Ok(Self::from_fixed(duration))
+            }
         }
     }
 }
@@ -184,6 +429,10 @@ impl fmt::Display for Interval {
 mod test {
     use super::*;
 
+    fn custom(duration: Duration) -> Interval {
+        Interval::custom(duration).unwrap()
+    }
+
     #[test]
     fn test_is_valid() {
         let tests = vec![
@@ -203,7 +452,7 @@ mod test {
             ),
         ];
         for (s, expected_array) in tests {
-            let time = Time::new(NaiveDateTime::from_str(s).unwrap().timestamp());
+            let time = Time::from_str(s).unwrap();
             assert_eq!(Interval::OneMinute.is_valid_time(&time), expected_array[0]);
             assert_eq!(
                 Interval::FiveMinutes.is_valid_time(&time),
@@ -219,6 +468,70 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_floor() {
+        let tests = vec![
+            (
+                "2023-01-01T00:00:00",
+                [
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                ],
+            ),
+            (
+                "2023-02-15T13:27:41",
+                [
+                    "2023-02-15T13:27:00",
+                    "2023-02-15T13:25:00",
+                    "2023-02-15T13:20:00",
+                    "2023-02-15T13:00:00",
+                    "2023-02-15T12:00:00",
+                    "2023-02-15T12:00:00",
+                    "2023-02-15T00:00:00",
+                    "2023-02-12T00:00:00",
+                    "2023-02-01T00:00:00",
+                ],
+            ),
+            (
+                "1969-12-31T23:59:59",
+                [
+                    "1969-12-31T23:59:00",
+                    "1969-12-31T23:55:00",
+                    "1969-12-31T23:50:00",
+                    "1969-12-31T23:00:00",
+                    "1969-12-31T22:00:00",
+                    "1969-12-31T20:00:00",
+                    "1969-12-31T00:00:00",
+                    "1969-12-28T00:00:00",
+                    "1969-12-01T00:00:00",
+                ],
+            ),
+        ];
+        for (s, expected_array) in tests {
+            let time = Time::from_str(s).unwrap();
+            let expected_array: Vec<Time> = expected_array
+                .into_iter()
+                .map(|s| Time::from_str(s).unwrap())
+                .collect();
+            assert_eq!(Interval::OneMinute.floor(&time), expected_array[0]);
+            assert_eq!(Interval::FiveMinutes.floor(&time), expected_array[1]);
+            assert_eq!(Interval::TenMinutes.floor(&time), expected_array[2]);
+            assert_eq!(Interval::OneHour.floor(&time), expected_array[3]);
+            assert_eq!(Interval::TwoHours.floor(&time), expected_array[4]);
+            assert_eq!(Interval::FourHours.floor(&time), expected_array[5]);
+            assert_eq!(Interval::OneDay.floor(&time), expected_array[6]);
+            assert_eq!(Interval::OneWeek.floor(&time), expected_array[7]);
+            assert_eq!(Interval::OneMonth.floor(&time), expected_array[8]);
+        }
+    }
+
     #[test]
     fn test_next() {
         let tests = vec![(
@@ -236,10 +549,10 @@ mod test {
             ],
         )];
         for (s, expected_array) in tests {
-            let time = Time::new(NaiveDateTime::from_str(s).unwrap().timestamp());
+            let time = Time::from_str(s).unwrap();
             let expected_array: Vec<Time> = expected_array
                 .into_iter()
-                .map(|s| Time::new(NaiveDateTime::from_str(s).unwrap().timestamp()))
+                .map(|s| Time::from_str(s).unwrap())
                 .collect();
             assert_eq!(Interval::OneMinute.next(time), expected_array[0]);
             assert_eq!(Interval::FiveMinutes.next(time), expected_array[1]);
@@ -252,4 +565,221 @@ mod test {
             assert_eq!(Interval::OneMonth.next(time), expected_array[8]);
         }
     }
+
+    #[test]
+    fn test_range() {
+        let time = |s: &str| Time::from_str(s).unwrap();
+        let tests = vec![
+            (
+                Interval::FiveMinutes,
+                "2023-01-01T00:03:00",
+                "2023-01-01T00:20:00",
+                vec![
+                    "2023-01-01T00:05:00",
+                    "2023-01-01T00:10:00",
+                    "2023-01-01T00:15:00",
+                ],
+            ),
+            (
+                Interval::OneWeek,
+                "2023-01-01T00:00:00",
+                "2023-01-22T00:00:01",
+                vec![
+                    "2023-01-01T00:00:00",
+                    "2023-01-08T00:00:00",
+                    "2023-01-15T00:00:00",
+                    "2023-01-22T00:00:00",
+                ],
+            ),
+            (
+                Interval::OneMonth,
+                "2022-11-01T00:00:00",
+                "2023-03-01T00:00:00",
+                vec![
+                    "2022-11-01T00:00:00",
+                    "2022-12-01T00:00:00",
+                    "2023-01-01T00:00:00",
+                    "2023-02-01T00:00:00",
+                ],
+            ),
+            (
+                Interval::OneDay,
+                "2023-01-02T00:00:00",
+                "2023-01-02T00:00:00",
+                vec![],
+            ),
+        ];
+        for (interval, from, to, expected) in tests {
+            let actual: Vec<Time> = interval.range(time(from), time(to)).collect();
+            let expected: Vec<Time> = expected.into_iter().map(time).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_sub_second_floor() {
+        let time = Time::from_nanos(1_676_467_661_999_999_999); // 2023-02-15T13:27:41.999999999
+        assert_eq!(Interval::OneMinute.floor(&time), Time::new(1_676_467_620));
+        assert_eq!(Interval::OneMonth.floor(&time), Time::new(1_675_209_600));
+        assert!(!Interval::OneMinute.is_valid_time(&Time::from_millis(60_001)));
+        let before_epoch = Time::from_millis(-1);
+        assert_eq!(Interval::OneMinute.floor(&before_epoch), Time::new(-60));
+        assert_eq!(
+            Interval::OneMonth.next(Time::from_millis(1_675_209_600_500)),
+            Time::new(1_677_628_800)
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let alignment = Alignment::default();
+        let last = Time::from_nanos(i64::MAX);
+        assert!(matches!(
+            Interval::OneDay.checked_next_aligned(last, &alignment),
+            Err(Error::TimeOutOfRange { .. })
+        ));
+        assert!(matches!(
+            Interval::OneMinute.checked_next_aligned(last, &alignment),
+            Err(Error::Overflow)
+        ));
+        let first = Time::from_nanos(i64::MIN);
+        assert!(matches!(
+            Interval::OneMonth.checked_floor_aligned(&first, &alignment),
+            Err(Error::TimeOutOfRange { .. })
+        ));
+        assert!(matches!(
+            Interval::OneMinute.checked_floor_aligned(&first, &alignment),
+            Err(Error::TimeOutOfRange { .. })
+        ));
+        assert!(!Interval::OneMinute.is_valid_time(&first));
+        assert!(!Interval::OneMonth.is_valid_time(&first));
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let tests = vec![
+            ("1s", Interval::OneSecond),
+            ("60s", Interval::OneMinute),
+            ("3m", Interval::ThreeMinutes),
+            ("15m", Interval::FifteenMinutes),
+            ("30m", Interval::ThirtyMinutes),
+            ("6h", Interval::SixHours),
+            ("8h", Interval::EightHours),
+            ("12h", Interval::TwelveHours),
+            ("1d", Interval::OneDay),
+            ("24h", custom(Duration::from_hours(24))),
+            ("3d", Interval::ThreeDays),
+            ("1w", Interval::OneWeek),
+            ("3M", Interval::ThreeMonths),
+            ("1y", Interval::OneYear),
+            ("100ms", custom(Duration::from_millis(100))),
+            ("90s", custom(Duration::from_seconds(90))),
+            ("2d", custom(Duration::from_days(2))),
+        ];
+        for (value, expected) in tests {
+            assert_eq!(Interval::from_str(value).unwrap(), expected);
+            assert_eq!(Interval::from_str(expected.as_str()).unwrap(), expected);
+        }
+        assert_eq!(custom(Duration::from_seconds(90)).as_str(), "90s");
+        assert_eq!(custom(Duration::from_minutes(120)).as_str(), "2h");
+        assert_eq!(custom(Duration::from_days(2)).as_str(), "48h");
+        assert_eq!(Interval::ThreeMonths.as_str(), "3M");
+        assert_eq!(&*custom(Duration::from_micros(1_500)), "1500us");
+
+        assert_eq!(custom(Duration::from_seconds(60)), Interval::OneMinute);
+        assert_eq!(custom(Duration::from_days(3)), Interval::ThreeDays);
+        assert!(matches!(
+            Interval::custom(Duration::ZERO),
+            Err(Error::InvalidInterval { .. })
+        ));
+        for value in [
+            "",
+            "m",
+            "0m",
+            "2w",
+            "2M",
+            "1x",
+            "-1m",
+            "1 m",
+            "99999999999999999999d",
+        ] {
+            assert!(matches!(
+                Interval::from_str(value),
+                Err(Error::InvalidInterval { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_additional_intervals() {
+        let time = Time::from_str("2023-08-19T13:27:41.25").unwrap();
+        let tests = vec![
+            (
+                Interval::OneSecond,
+                "2023-08-19T13:27:41",
+                "2023-08-19T13:27:42",
+            ),
+            (
+                Interval::ThreeMinutes,
+                "2023-08-19T13:27:00",
+                "2023-08-19T13:30:00",
+            ),
+            (
+                Interval::FifteenMinutes,
+                "2023-08-19T13:15:00",
+                "2023-08-19T13:30:00",
+            ),
+            (
+                Interval::ThirtyMinutes,
+                "2023-08-19T13:00:00",
+                "2023-08-19T13:30:00",
+            ),
+            (
+                Interval::SixHours,
+                "2023-08-19T12:00:00",
+                "2023-08-19T18:00:00",
+            ),
+            (
+                Interval::EightHours,
+                "2023-08-19T08:00:00",
+                "2023-08-19T16:00:00",
+            ),
+            (
+                Interval::TwelveHours,
+                "2023-08-19T12:00:00",
+                "2023-08-20T00:00:00",
+            ),
+            (
+                Interval::ThreeDays,
+                "2023-08-18T00:00:00",
+                "2023-08-21T00:00:00",
+            ),
+            (
+                Interval::ThreeMonths,
+                "2023-07-01T00:00:00",
+                "2023-10-01T00:00:00",
+            ),
+            (
+                Interval::OneYear,
+                "2023-01-01T00:00:00",
+                "2024-01-01T00:00:00",
+            ),
+            (
+                custom(Duration::from_millis(100)),
+                "2023-08-19T13:27:41.2",
+                "2023-08-19T13:27:41.3",
+            ),
+        ];
+        for (interval, floor, next) in tests {
+            let floor = Time::from_str(floor).unwrap();
+            assert_eq!(interval.floor(&time), floor, "{}", interval);
+            assert_eq!(
+                interval.next(floor),
+                Time::from_str(next).unwrap(),
+                "{}",
+                interval
+            );
+            assert!(interval.is_valid_time(&floor));
+        }
+    }
 }
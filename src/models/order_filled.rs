@@ -1,8 +1,39 @@
-use super::{Asset, AssetPair, Price, Quantity};
+use super::{Asset, AssetPair, Price, Quantity, Side, Time};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderFilled<A: Asset> {
     pub asset_pair: AssetPair<A>,
     pub price: Price,
     pub quantity: Quantity,
+    /// execution time of the trade.
+    pub time: Time,
+    /// exchange assigned identifier of the trade, if any.
+    pub trade_id: Option<u64>,
+    /// taker side, when the venue reports it.
+    pub side: Option<Side>,
+}
+
+impl<A: Asset> OrderFilled<A> {
+    /// a fill without a trade id or a taker side.
+    pub fn new(asset_pair: AssetPair<A>, price: Price, quantity: Quantity, time: Time) -> Self {
+        Self {
+            asset_pair,
+            price,
+            quantity,
+            time,
+            trade_id: None,
+            side: None,
+        }
+    }
+
+    pub fn with_trade_id(mut self, trade_id: u64) -> Self {
+        self.trade_id = Some(trade_id);
+        self
+    }
+
+    pub fn with_side(mut self, side: Side) -> Self {
+        self.side = Some(side);
+        self
+    }
 }
@@ -1,26 +1,15 @@
-use std::cmp::Ordering;
+use super::fixed_point::impl_fixed_point;
 
-#[derive(Debug, Copy, Clone, Eq)]
+#[derive(Debug, Copy, Clone)]
 pub struct Quantity {
     pub num: u64,
     pub scale: u32,
 }
 
-impl PartialEq for Quantity {
-    fn eq(&self, other: &Self) -> bool {
-        self.scale == other.scale && self.num == other.num
+impl Quantity {
+    pub fn new(num: u64, scale: u32) -> Self {
+        Self { num, scale }
     }
 }
 
-impl Ord for Quantity {
-    fn cmp(&self, other: &Self) -> Ordering {
-        assert_eq!(self.scale, other.scale);
-        self.num.cmp(&other.num)
-    }
-}
-
-impl PartialOrd for Quantity {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
+impl_fixed_point!(Quantity);
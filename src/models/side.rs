@@ -0,0 +1,7 @@
+/// side of the taker, the order that removed liquidity from the book.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Side {
+    Buy,
+    Sell,
+}
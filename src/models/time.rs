@@ -1,19 +1,195 @@
+use crate::{Error, Result};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
 use std::cmp::Ordering;
-use std::ops::Add;
+use std::fmt;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::str::FromStr;
 
+const NANOS_PER_MICRO: i64 = 1_000;
+const NANOS_PER_MILLI: i64 = 1_000_000;
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+const fn mul_or_panic(value: u64, factor: u64) -> u64 {
+    match value.checked_mul(factor) {
+        Some(product) => product,
+        None => panic!("duration overflow"),
+    }
+}
+
+fn checked_timestamp_from(value: i64, factor: i64, unit: &str) -> Result<i64> {
+    value
+        .checked_mul(factor)
+        .ok_or_else(|| Error::TimeOutOfRange {
+            value: format!("{}{}", value, unit),
+        })
+}
+
+/// point in time since the unix epoch, with nanosecond resolution.
+///
+/// every `Time` is stored in nanoseconds rather than at a configurable precision, so times
+/// built from seconds, milliseconds or nanoseconds compare and combine without conversions.
+/// the representable range is roughly the years 1678 through 2262.
 #[derive(Debug, Copy, Clone, Eq)]
 pub struct Time {
-    timestamp: i64, // in sec
+    timestamp: i64, // in nanosec
 }
 
 impl Time {
+    /// `timestamp` in seconds, panics outside of the representable range,
+    /// see `Time::checked_from_seconds`.
     pub fn new(timestamp: i64) -> Self {
-        Self { timestamp }
+        Self::checked_from_seconds(timestamp).expect("time out of range")
+    }
+
+    /// panics outside of the representable range, see `Time::checked_from_millis`.
+    pub fn from_millis(millis: i64) -> Self {
+        Self::checked_from_millis(millis).expect("time out of range")
+    }
+
+    /// panics outside of the representable range, see `Time::checked_from_micros`.
+    pub fn from_micros(micros: i64) -> Self {
+        Self::checked_from_micros(micros).expect("time out of range")
+    }
+
+    /// `Error::TimeOutOfRange` outside of the representable range.
+    pub fn checked_from_seconds(seconds: i64) -> Result<Self> {
+        checked_timestamp_from(seconds, NANOS_PER_SEC, "s").map(Self::from_nanos)
+    }
+
+    /// `Error::TimeOutOfRange` outside of the representable range.
+    pub fn checked_from_millis(millis: i64) -> Result<Self> {
+        checked_timestamp_from(millis, NANOS_PER_MILLI, "ms").map(Self::from_nanos)
+    }
+
+    /// `Error::TimeOutOfRange` outside of the representable range.
+    pub fn checked_from_micros(micros: i64) -> Result<Self> {
+        checked_timestamp_from(micros, NANOS_PER_MICRO, "us").map(Self::from_nanos)
+    }
+
+    pub fn from_nanos(nanos: i64) -> Self {
+        Self { timestamp: nanos }
     }
 
+    /// whole seconds, rounded toward negative infinity.
     pub fn value(&self) -> i64 {
+        self.timestamp.div_euclid(NANOS_PER_SEC)
+    }
+
+    /// whole milliseconds, rounded toward negative infinity.
+    pub fn as_millis(&self) -> i64 {
+        self.timestamp.div_euclid(NANOS_PER_MILLI)
+    }
+
+    /// whole microseconds, rounded toward negative infinity.
+    pub fn as_micros(&self) -> i64 {
+        self.timestamp.div_euclid(NANOS_PER_MICRO)
+    }
+
+    pub fn as_nanos(&self) -> i64 {
         self.timestamp
     }
+
+    /// nanoseconds past `value()`, always in `[0, 1_000_000_000)`.
+    pub fn subsec_nanos(&self) -> u32 {
+        self.timestamp.rem_euclid(NANOS_PER_SEC) as u32
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Result<Self> {
+        let timestamp = i128::from(self.timestamp) + i128::from(duration.nanos);
+        i64::try_from(timestamp)
+            .map(Self::from_nanos)
+            .map_err(|_| Error::Overflow)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Result<Self> {
+        let timestamp = i128::from(self.timestamp) - i128::from(duration.nanos);
+        i64::try_from(timestamp)
+            .map(Self::from_nanos)
+            .map_err(|_| Error::Underflow)
+    }
+
+    pub fn saturating_add(&self, duration: Duration) -> Self {
+        self.checked_add(duration)
+            .unwrap_or_else(|_| Self::from_nanos(i64::MAX))
+    }
+
+    pub fn saturating_sub(&self, duration: Duration) -> Self {
+        self.checked_sub(duration)
+            .unwrap_or_else(|_| Self::from_nanos(i64::MIN))
+    }
+
+    /// `Error::Underflow` if `earlier` is after this time.
+    pub fn checked_duration_since(&self, earlier: Time) -> Result<Duration> {
+        let nanos = i128::from(self.timestamp) - i128::from(earlier.timestamp);
+        u64::try_from(nanos)
+            .map(Duration::from_nanos)
+            .map_err(|_| Error::Underflow)
+    }
+
+    /// zero if `earlier` is after this time.
+    pub fn saturating_duration_since(&self, earlier: Time) -> Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+impl From<Time> for DateTime<Utc> {
+    fn from(time: Time) -> Self {
+        DateTime::from_timestamp(time.value(), time.subsec_nanos())
+            .expect("the range of Time is within the range of chrono")
+    }
+}
+
+impl From<Time> for NaiveDateTime {
+    fn from(time: Time) -> Self {
+        DateTime::<Utc>::from(time).naive_utc()
+    }
+}
+
+impl TryFrom<DateTime<Utc>> for Time {
+    type Error = Error;
+
+    fn try_from(dt: DateTime<Utc>) -> Result<Self> {
+        dt.timestamp()
+            .checked_mul(NANOS_PER_SEC)
+            .and_then(|nanos| nanos.checked_add(i64::from(dt.timestamp_subsec_nanos())))
+            .map(Self::from_nanos)
+            .ok_or_else(|| Error::TimeOutOfRange {
+                value: dt.to_rfc3339(),
+            })
+    }
+}
+
+impl TryFrom<NaiveDateTime> for Time {
+    type Error = Error;
+
+    /// the naive date time is taken as UTC.
+    fn try_from(dt: NaiveDateTime) -> Result<Self> {
+        Self::try_from(dt.and_utc())
+    }
+}
+
+impl fmt::Display for Time {
+    /// RFC 3339 in UTC, with only as many fractional digits as needed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dt: DateTime<Utc> = (*self).into();
+        write!(f, "{}", dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl FromStr for Time {
+    type Err = Error;
+
+    /// RFC 3339, or a date time without offset taken as UTC, like `2023-01-01T00:00:00`.
+    fn from_str(value: &str) -> Result<Self> {
+        let dt = match DateTime::parse_from_rfc3339(value) {
+            Ok(dt) => dt.naive_utc(),
+            Err(_) => NaiveDateTime::from_str(value).map_err(|_| Error::InvalidTime {
+                value: value.to_owned(),
+            })?,
+        };
+        Self::try_from(dt)
+    }
 }
 
 impl PartialEq for Time {
@@ -34,52 +210,203 @@ impl PartialOrd for Time {
     }
 }
 
+/// panics when the result is out of range, see `Time::checked_add`.
 impl Add<Duration> for Time {
     type Output = Time;
     fn add(self, other: Duration) -> Self::Output {
-        Self {
-            timestamp: self.timestamp + other.seconds as i64,
-        }
+        self.checked_add(other).expect("time out of range")
+    }
+}
+
+impl AddAssign<Duration> for Time {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+/// panics when the result is out of range, see `Time::checked_sub`.
+impl Sub<Duration> for Time {
+    type Output = Time;
+    fn sub(self, other: Duration) -> Self::Output {
+        self.checked_sub(other).expect("time out of range")
+    }
+}
+
+impl SubAssign<Duration> for Time {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+/// panics when `other` is after `self`, see `Time::checked_duration_since`.
+impl Sub<Time> for Time {
+    type Output = Duration;
+    fn sub(self, other: Time) -> Self::Output {
+        self.checked_duration_since(other)
+            .expect("time is before the subtracted time")
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq)]
 pub struct Duration {
-    seconds: u64,
+    nanos: u64,
 }
 
 impl Duration {
+    pub const ZERO: Self = Self::from_nanos(0);
+
+    /// whole seconds, the sub-second part is truncated.
     pub fn value(&self) -> u64 {
-        self.seconds
+        self.nanos / NANOS_PER_SEC as u64
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.nanos / NANOS_PER_MILLI as u64
+    }
+
+    pub fn as_micros(&self) -> u64 {
+        self.nanos / NANOS_PER_MICRO as u64
+    }
+
+    pub fn as_nanos(&self) -> u64 {
+        self.nanos
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.nanos == 0
+    }
+
+    pub fn checked_add(&self, other: Duration) -> Result<Self> {
+        self.nanos
+            .checked_add(other.nanos)
+            .map(Self::from_nanos)
+            .ok_or(Error::Overflow)
+    }
+
+    pub fn checked_sub(&self, other: Duration) -> Result<Self> {
+        self.nanos
+            .checked_sub(other.nanos)
+            .map(Self::from_nanos)
+            .ok_or(Error::Underflow)
+    }
+
+    pub fn checked_mul(&self, factor: u64) -> Result<Self> {
+        self.nanos
+            .checked_mul(factor)
+            .map(Self::from_nanos)
+            .ok_or(Error::Overflow)
+    }
+
+    pub fn saturating_add(&self, other: Duration) -> Self {
+        Self::from_nanos(self.nanos.saturating_add(other.nanos))
+    }
+
+    pub fn saturating_sub(&self, other: Duration) -> Self {
+        Self::from_nanos(self.nanos.saturating_sub(other.nanos))
     }
 }
 
+/// the constructors panic when the duration does not fit in u64 nanoseconds.
 impl Duration {
+    #[inline]
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self { nanos }
+    }
+    #[inline]
+    pub const fn from_micros(micros: u64) -> Self {
+        Self::from_nanos(mul_or_panic(micros, NANOS_PER_MICRO as u64))
+    }
+    #[inline]
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::from_nanos(mul_or_panic(millis, NANOS_PER_MILLI as u64))
+    }
+    #[inline]
+    pub const fn from_seconds(seconds: u64) -> Self {
+        Self::from_nanos(mul_or_panic(seconds, NANOS_PER_SEC as u64))
+    }
     #[inline]
     pub const fn from_minutes(minutes: u64) -> Self {
-        Self {
-            seconds: minutes * 60,
-        }
+        Self::from_seconds(mul_or_panic(minutes, 60))
     }
     #[inline]
     pub const fn from_hours(hours: u64) -> Self {
-        Self::from_minutes(hours * 60)
+        Self::from_minutes(mul_or_panic(hours, 60))
     }
     #[inline]
     pub const fn from_days(days: u64) -> Self {
-        Self::from_hours(days * 24)
+        Self::from_hours(mul_or_panic(days, 24))
+    }
+    #[inline]
+    pub const fn from_weeks(weeks: u64) -> Self {
+        Self::from_days(mul_or_panic(weeks, 7))
+    }
+}
+
+/// panics on overflow, see `Duration::checked_add`.
+impl Add for Duration {
+    type Output = Duration;
+    fn add(self, other: Duration) -> Self::Output {
+        self.checked_add(other).expect("duration overflow")
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+/// panics when `other` is longer, see `Duration::checked_sub`.
+impl Sub for Duration {
+    type Output = Duration;
+    fn sub(self, other: Duration) -> Self::Output {
+        self.checked_sub(other).expect("duration underflow")
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl From<Duration> for chrono::Duration {
+    fn from(duration: Duration) -> Self {
+        let seconds = (duration.nanos / NANOS_PER_SEC as u64) as i64;
+        let nanos = (duration.nanos % NANOS_PER_SEC as u64) as i64;
+        chrono::Duration::seconds(seconds) + chrono::Duration::nanoseconds(nanos)
+    }
+}
+
+impl TryFrom<chrono::Duration> for Duration {
+    type Error = Error;
+
+    /// `Error::Underflow` for negative durations, `Error::Overflow` beyond u64 nanoseconds.
+    fn try_from(duration: chrono::Duration) -> Result<Self> {
+        if duration < chrono::Duration::zero() {
+            return Err(Error::Underflow);
+        }
+        let seconds = duration.num_seconds();
+        let nanos = (duration - chrono::Duration::seconds(seconds))
+            .num_nanoseconds()
+            .unwrap_or_default();
+        (seconds as u64)
+            .checked_mul(NANOS_PER_SEC as u64)
+            .and_then(|total| total.checked_add(nanos as u64))
+            .map(Self::from_nanos)
+            .ok_or(Error::Overflow)
     }
 }
 
 impl PartialEq for Duration {
     fn eq(&self, other: &Self) -> bool {
-        self.seconds == other.seconds
+        self.nanos == other.nanos
     }
 }
 
 impl Ord for Duration {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.seconds.cmp(&other.seconds)
+        self.nanos.cmp(&other.nanos)
     }
 }
 
@@ -88,3 +415,116 @@ impl PartialOrd for Duration {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_precision() {
+        let time = Time::from_nanos(1_672_531_200_123_456_789);
+        assert_eq!(time.value(), 1_672_531_200);
+        assert_eq!(time.as_millis(), 1_672_531_200_123);
+        assert_eq!(time.as_micros(), 1_672_531_200_123_456);
+        assert_eq!(time.subsec_nanos(), 123_456_789);
+        assert_eq!(Time::from_millis(1_500), Time::from_micros(1_500_000));
+        assert!(Time::new(1) < Time::from_millis(1_001));
+        assert_eq!(
+            Time::checked_from_millis(1_500).unwrap(),
+            Time::from_millis(1_500)
+        );
+        // 2262-04-12, the last representable second
+        assert!(Time::checked_from_seconds(9_223_372_036).is_ok());
+        for result in [
+            Time::checked_from_seconds(9_223_372_037),
+            Time::checked_from_millis(i64::MIN),
+            Time::checked_from_micros(i64::MAX),
+        ] {
+            assert!(matches!(result, Err(Error::TimeOutOfRange { .. })));
+        }
+
+        let before_epoch = Time::from_millis(-1);
+        assert_eq!(before_epoch.value(), -1);
+        assert_eq!(before_epoch.subsec_nanos(), 999_000_000);
+
+        assert_eq!(
+            Time::from_millis(999) + Duration::from_millis(1),
+            Time::new(1)
+        );
+        assert_eq!(Duration::from_micros(1_500).as_millis(), 1);
+        assert_eq!(Duration::from_minutes(1).value(), 60);
+    }
+
+    #[test]
+    fn test_chrono_interop() {
+        let time = Time::from_str("2023-02-15T13:27:41.5Z").unwrap();
+        assert_eq!(time, Time::from_millis(1_676_467_661_500));
+        assert_eq!(Time::from_str("2023-02-15T22:27:41.5+09:00").unwrap(), time);
+        assert_eq!(Time::from_str("2023-02-15T13:27:41.5").unwrap(), time);
+        assert_eq!(time.to_string(), "2023-02-15T13:27:41.500Z");
+        assert_eq!(Time::new(0).to_string(), "1970-01-01T00:00:00Z");
+        assert!(matches!(
+            Time::from_str("yesterday"),
+            Err(Error::InvalidTime { .. })
+        ));
+        assert!(matches!(
+            Time::from_str("2263-01-01T00:00:00Z"),
+            Err(Error::TimeOutOfRange { .. })
+        ));
+
+        let dt: DateTime<Utc> = time.into();
+        assert_eq!(dt.timestamp_millis(), 1_676_467_661_500);
+        assert_eq!(Time::try_from(dt).unwrap(), time);
+        let naive: NaiveDateTime = time.into();
+        assert_eq!(Time::try_from(naive).unwrap(), time);
+
+        let duration = Duration::from_millis(90_500);
+        let chrono_duration: chrono::Duration = duration.into();
+        assert_eq!(chrono_duration.num_milliseconds(), 90_500);
+        assert_eq!(Duration::try_from(chrono_duration).unwrap(), duration);
+        assert!(matches!(
+            Duration::try_from(chrono::Duration::seconds(-1)),
+            Err(Error::Underflow)
+        ));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let start = Time::from_str("2023-01-01T00:00:00Z").unwrap();
+        let end = Time::from_str("2023-01-08T00:00:01.5Z").unwrap();
+        let elapsed = Duration::from_weeks(1) + Duration::from_millis(1_500);
+        assert_eq!(end - start, elapsed);
+        assert_eq!(end - elapsed, start);
+        let mut time = start;
+        time += elapsed;
+        assert_eq!(time, end);
+        time -= Duration::from_seconds(1);
+        assert_eq!(time - start, elapsed - Duration::from_seconds(1));
+
+        assert!(matches!(
+            start.checked_duration_since(end),
+            Err(Error::Underflow)
+        ));
+        assert_eq!(start.saturating_duration_since(end), Duration::ZERO);
+        assert_eq!(
+            Time::new(-1) - Time::from_nanos(i64::MIN),
+            Duration::from_nanos(i64::MAX as u64 - NANOS_PER_SEC as u64 + 1)
+        );
+        let max = Duration::from_nanos(u64::MAX);
+        assert_eq!(
+            Time::from_nanos(i64::MIN).checked_add(max).unwrap(),
+            Time::from_nanos(i64::MAX)
+        );
+        assert!(matches!(start.checked_add(max), Err(Error::Overflow)));
+        assert!(matches!(start.checked_sub(max), Err(Error::Underflow)));
+        assert_eq!(start.saturating_add(max), Time::from_nanos(i64::MAX));
+        assert_eq!(start.saturating_sub(max), Time::from_nanos(i64::MIN));
+
+        assert!(matches!(max.checked_add(elapsed), Err(Error::Overflow)));
+        assert!(matches!(elapsed.checked_sub(max), Err(Error::Underflow)));
+        assert!(matches!(max.checked_mul(2), Err(Error::Overflow)));
+        assert_eq!(max.saturating_add(elapsed), max);
+        assert!(elapsed.saturating_sub(max).is_zero());
+        assert_eq!(Duration::from_seconds(60), Duration::from_minutes(1));
+    }
+}
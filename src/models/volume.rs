@@ -1,8 +1,8 @@
-use super::Quantity;
-use std::cmp::Ordering;
-use std::ops::AddAssign;
+use super::fixed_point::{self, impl_fixed_point};
+use super::{Price, Quantity};
+use crate::Result;
 
-#[derive(Debug, Copy, Clone, Eq)]
+#[derive(Debug, Copy, Clone)]
 pub struct Volume {
     pub num: u64,
     pub scale: u32,
@@ -16,27 +16,22 @@ impl Volume {
         }
     }
 
-    pub fn add_quantity(&mut self, quantity: &Quantity) {
-        assert_eq!(self.scale, quantity.scale);
-        self.num.add_assign(quantity.num);
+    pub fn zero(scale: u32) -> Self {
+        Self { num: 0, scale }
     }
-}
 
-impl PartialEq for Volume {
-    fn eq(&self, other: &Self) -> bool {
-        self.scale == other.scale && self.num == other.num
+    /// exact `price * quantity`, at the sum of both scales.
+    pub fn new_quote(price: &Price, quantity: &Quantity) -> Result<Self> {
+        let (num, scale) =
+            fixed_point::mul((price.num, price.scale), (quantity.num, quantity.scale))?;
+        Ok(Self { num, scale })
     }
-}
 
-impl Ord for Volume {
-    fn cmp(&self, other: &Self) -> Ordering {
-        assert_eq!(self.scale, other.scale);
-        self.num.cmp(&other.num)
+    /// the volume is left untouched on error.
+    pub fn add_quantity(&mut self, quantity: &Quantity) -> Result<()> {
+        *self = self.checked_add(&Self::new(*quantity))?;
+        Ok(())
     }
 }
 
-impl PartialOrd for Volume {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
+impl_fixed_point!(Volume);
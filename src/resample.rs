@@ -0,0 +1,382 @@
+use crate::models::{Alignment, Asset, AssetPair, Candlestick, Duration, Interval, Time};
+use crate::{Error, Result};
+
+/// Combines candlesticks of an interval into candlesticks of a coarser interval,
+/// e.g. `1m` into `1h`, without replaying the filled orders.
+/// Candlesticks are pushed in open time order. Target candlesticks missing some of their
+/// source candlesticks are not complete, and target buckets without any are returned
+/// without data.
+pub struct CandlestickResampler<A: Asset> {
+    asset_pair: AssetPair<A>,
+    source: Interval,
+    target: Interval,
+    alignment: Alignment,
+    current: Option<Candlestick<A>>,
+    /// every source candlestick of `current` so far was pushed.
+    covered: bool,
+    last_open_time: Option<Time>,
+}
+
+impl<A: Asset> CandlestickResampler<A> {
+    /// `Error::IncompatibleIntervals` unless the source buckets tile the target buckets.
+    pub fn new(asset_pair: AssetPair<A>, source: Interval, target: Interval) -> Result<Self> {
+        Self::new_with_alignment(asset_pair, source, target, Alignment::default())
+    }
+
+    pub fn new_with_alignment(
+        asset_pair: AssetPair<A>,
+        source: Interval,
+        target: Interval,
+        alignment: Alignment,
+    ) -> Result<Self> {
+        if !source.is_divisor_of(&target, &alignment) {
+            return Err(Error::IncompatibleIntervals { source, target });
+        }
+        Ok(Self {
+            asset_pair,
+            source,
+            target,
+            alignment,
+            current: None,
+            covered: false,
+            last_open_time: None,
+        })
+    }
+
+    /// target candlestick which did not receive its last source candlestick yet.
+    pub fn incomplete(&self) -> Option<&Candlestick<A>> {
+        self.current.as_ref()
+    }
+
+    /// returns the target candlesticks closed by the candlestick, and the empty target
+    /// buckets skipped before it.
+    /// A target candlestick is closed by its last source candlestick or by a later one.
+    pub fn push(&mut self, candlestick: Candlestick<A>) -> Result<Vec<Resampled<A>>> {
+        self.validate(&candlestick)?;
+        let source_next = self
+            .source
+            .next_aligned(candlestick.open_time, &self.alignment);
+        let open_time = self
+            .target
+            .floor_aligned(&candlestick.open_time, &self.alignment);
+        let target_next = self.target.next_aligned(open_time, &self.alignment);
+        let last_instant = source_next.saturating_sub(Duration::from_nanos(1));
+        if self.target.floor_aligned(&last_instant, &self.alignment) != open_time {
+            return Err(Error::MisalignedTime {
+                interval: self.target,
+                time: candlestick.open_time,
+            });
+        }
+
+        let data = match &self.current {
+            Some(current) if current.open_time == open_time => current.data.as_ref(),
+            _ => None,
+        };
+        // buckets without data leave the target data as is.
+        let data = match (data, candlestick.data) {
+            (Some(data), Some(later)) => Some(data.merge(&later)?),
+            (data, later) => later.or_else(|| data.cloned()),
+        };
+
+        let mut closed = vec![];
+        match self.current.take() {
+            Some(current) if current.open_time == open_time => {
+                let last_open_time = self.last_open_time.expect("current has a source");
+                let previous_next = self.source.next_aligned(last_open_time, &self.alignment);
+                self.covered &= previous_next == candlestick.open_time;
+            }
+            previous => {
+                // the previous target candlestick would have been closed by its last source.
+                closed.extend(previous.map(|candlestick| Resampled {
+                    candlestick,
+                    complete: false,
+                }));
+                if let Some(last_open_time) = self.last_open_time {
+                    let last = self.target.floor_aligned(&last_open_time, &self.alignment);
+                    let gap_start = self.target.next_aligned(last, &self.alignment);
+                    let gap = self
+                        .target
+                        .range_aligned(gap_start, open_time, &self.alignment)
+                        .map(|gap_open_time| Resampled {
+                            candlestick: Candlestick::new(
+                                self.asset_pair.clone(),
+                                gap_open_time,
+                                self.target,
+                            ),
+                            complete: false,
+                        });
+                    closed.extend(gap);
+                }
+                self.covered = candlestick.open_time == open_time;
+            }
+        }
+        let current =
+            Candlestick::new_with_data(self.asset_pair.clone(), open_time, self.target, data);
+        self.last_open_time = Some(candlestick.open_time);
+        if source_next == target_next {
+            closed.push(Resampled {
+                candlestick: current,
+                complete: self.covered,
+            });
+        } else {
+            self.current = Some(current);
+        }
+        Ok(closed)
+    }
+
+    /// takes the incomplete target candlestick, see `incomplete`.
+    pub fn flush(&mut self) -> Option<Candlestick<A>> {
+        self.current.take()
+    }
+
+    fn validate(&self, candlestick: &Candlestick<A>) -> Result<()> {
+        if candlestick.asset_pair != self.asset_pair {
+            return Err(Error::asset_pair_mismatch(
+                &self.asset_pair,
+                &candlestick.asset_pair,
+            ));
+        }
+        if candlestick.interval != self.source {
+            return Err(Error::IntervalMismatch {
+                expected: self.source,
+                actual: candlestick.interval,
+            });
+        }
+        if !self
+            .source
+            .is_valid_time_aligned(&candlestick.open_time, &self.alignment)
+        {
+            return Err(Error::MisalignedTime {
+                interval: self.source,
+                time: candlestick.open_time,
+            });
+        }
+        match self.last_open_time {
+            Some(current) if current >= candlestick.open_time => Err(Error::RegressingTime {
+                current,
+                time: candlestick.open_time,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// target candlestick of `CandlestickResampler` and `resample`.
+#[derive(Debug, Clone)]
+pub struct Resampled<A: Asset> {
+    pub candlestick: Candlestick<A>,
+    /// every source candlestick of the bucket was pushed.
+    pub complete: bool,
+}
+
+/// resamples candlesticks of a single asset pair and interval, sorted by open time,
+/// into every target bucket from the first candlestick to the last one.
+/// The source asset pair and interval are taken from the first one.
+pub fn resample<A: Asset>(
+    candlesticks: impl IntoIterator<Item = Candlestick<A>>,
+    target: Interval,
+    alignment: Alignment,
+) -> Result<Vec<Resampled<A>>> {
+    let mut candlesticks = candlesticks.into_iter().peekable();
+    let (asset_pair, source) = match candlesticks.peek() {
+        Some(first) => (first.asset_pair.clone(), first.interval),
+        None => return Ok(vec![]),
+    };
+    let mut resampler =
+        CandlestickResampler::new_with_alignment(asset_pair, source, target, alignment)?;
+    let mut resampled = vec![];
+    for candlestick in candlesticks {
+        resampled.extend(resampler.push(candlestick)?);
+    }
+    resampled.extend(resampler.flush().map(|candlestick| Resampled {
+        candlestick,
+        complete: false,
+    }));
+    Ok(resampled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{CandlestickData, Price, Quantity};
+    use crate::test_fixtures::asset_pair;
+    use std::str::FromStr;
+
+    fn candlestick(interval: Interval, open_time: &str, prices: &[u64]) -> Candlestick<String> {
+        let mut data: Option<CandlestickData> = None;
+        for price in prices {
+            let (price, quantity) = (Price::new(*price, 0), Quantity::new(1, 0));
+            match &mut data {
+                Some(data) => data.add_price_and_quantity(price, quantity).unwrap(),
+                None => {
+                    data =
+                        Some(CandlestickData::new_with_price_and_quantity(price, quantity).unwrap())
+                }
+            }
+        }
+        Candlestick::new_with_data(
+            asset_pair(),
+            Time::from_str(open_time).unwrap(),
+            interval,
+            data,
+        )
+    }
+
+    #[test]
+    fn test_resample() {
+        let source = vec![
+            candlestick(Interval::OneMinute, "2023-01-01T00:00:00", &[10, 12]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:01:00", &[15, 8]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:02:00", &[]),
+            // 00:03 is missing
+            candlestick(Interval::OneMinute, "2023-01-01T00:04:00", &[9]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:05:00", &[]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:06:00", &[]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:07:00", &[7]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:08:00", &[]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:09:00", &[]),
+            // 00:10 through 00:19 are missing
+            candlestick(Interval::OneMinute, "2023-01-01T00:20:00", &[11]),
+            candlestick(Interval::OneMinute, "2023-01-01T00:21:00", &[13]),
+        ];
+        let resampled = resample(source, Interval::FiveMinutes, Alignment::default()).unwrap();
+        let buckets: Vec<(String, bool)> = resampled
+            .iter()
+            .map(|resampled| {
+                let open_time = resampled.candlestick.open_time.to_string();
+                (open_time, resampled.complete)
+            })
+            .collect();
+        let expected = vec![
+            ("2023-01-01T00:00:00Z", false),
+            ("2023-01-01T00:05:00Z", true),
+            ("2023-01-01T00:10:00Z", false),
+            ("2023-01-01T00:15:00Z", false),
+            ("2023-01-01T00:20:00Z", false),
+        ];
+        let expected: Vec<(String, bool)> = expected
+            .into_iter()
+            .map(|(open_time, complete)| (open_time.to_owned(), complete))
+            .collect();
+        assert_eq!(buckets, expected);
+        assert!(resampled
+            .iter()
+            .all(|resampled| resampled.candlestick.interval == Interval::FiveMinutes));
+
+        let data = resampled[0].candlestick.data.as_ref().unwrap();
+        let ohlc = (data.open.num, data.high.num, data.low.num, data.close.num);
+        assert_eq!(ohlc, (10, 15, 8, 9));
+        assert_eq!(data.volume.num, 5);
+        assert_eq!(data.quote_volume.unwrap().num, 54);
+        assert_eq!(data.trade_count, 5);
+        assert_eq!(resampled[1].candlestick.data.as_ref().unwrap().close.num, 7);
+        assert!(resampled[2].candlestick.data.is_none());
+        assert!(resampled[3].candlestick.data.is_none());
+        let data = resampled[4].candlestick.data.as_ref().unwrap();
+        assert_eq!((data.open.num, data.close.num), (11, 13));
+    }
+
+    #[test]
+    fn test_resampler_gap() {
+        let mut resampler =
+            CandlestickResampler::new(asset_pair(), Interval::OneMinute, Interval::FiveMinutes)
+                .unwrap();
+        // the last source candlestick closes its target bucket even though 00:01 is missing.
+        let mut closed = vec![];
+        for open_time in ["2023-01-01T00:00:00", "2023-01-01T00:02:00"] {
+            closed.extend(
+                resampler
+                    .push(candlestick(Interval::OneMinute, open_time, &[1]))
+                    .unwrap(),
+            );
+        }
+        assert!(closed.is_empty());
+        for minute in 3..5 {
+            let open_time = format!("2023-01-01T00:{:02}:00", minute);
+            closed.extend(
+                resampler
+                    .push(candlestick(Interval::OneMinute, &open_time, &[1]))
+                    .unwrap(),
+            );
+        }
+        assert_eq!(closed.len(), 1);
+        assert!(!closed[0].complete);
+        assert_eq!(closed[0].candlestick.data.as_ref().unwrap().trade_count, 4);
+        assert!(resampler.incomplete().is_none());
+    }
+
+    #[test]
+    fn test_calendar_resample() {
+        let mut resampler =
+            CandlestickResampler::new(asset_pair(), Interval::OneDay, Interval::OneMonth).unwrap();
+        let mut closed = vec![];
+        for day in 1..=28 {
+            let open_time = format!("2023-02-{:02}T00:00:00", day);
+            let source = candlestick(Interval::OneDay, &open_time, &[day]);
+            closed.extend(resampler.push(source).unwrap());
+            assert_eq!(resampler.incomplete().is_some(), day < 28);
+        }
+        assert_eq!(closed.len(), 1);
+        assert!(closed[0].complete);
+        let data = closed[0].candlestick.data.as_ref().unwrap();
+        assert_eq!(
+            (data.open.num, data.close.num, data.trade_count),
+            (1, 28, 28)
+        );
+        assert!(resampler.flush().is_none());
+    }
+
+    #[test]
+    fn test_invalid_resample() {
+        let incompatible = vec![
+            (Interval::OneMonth, Interval::OneWeek),
+            (Interval::OneWeek, Interval::OneMonth),
+            (Interval::OneHour, Interval::FiveMinutes),
+            (
+                Interval::custom(Duration::from_minutes(7)).unwrap(),
+                Interval::OneHour,
+            ),
+            (Interval::ThreeDays, Interval::OneWeek),
+            (Interval::OneYear, Interval::ThreeMonths),
+        ];
+        for (source, target) in incompatible {
+            assert!(matches!(
+                CandlestickResampler::new(asset_pair(), source, target),
+                Err(Error::IncompatibleIntervals { .. })
+            ));
+        }
+
+        let mut resampler =
+            CandlestickResampler::new(asset_pair(), Interval::OneMinute, Interval::OneHour)
+                .unwrap();
+        resampler
+            .push(candlestick(
+                Interval::OneMinute,
+                "2023-01-01T00:01:00",
+                &[1],
+            ))
+            .unwrap();
+        assert!(matches!(
+            resampler.push(candlestick(
+                Interval::FiveMinutes,
+                "2023-01-01T00:05:00",
+                &[1]
+            )),
+            Err(Error::IntervalMismatch { .. })
+        ));
+        assert!(matches!(
+            resampler.push(candlestick(
+                Interval::OneMinute,
+                "2023-01-01T00:01:00",
+                &[1]
+            )),
+            Err(Error::RegressingTime { .. })
+        ));
+        let mut misaligned = candlestick(Interval::OneMinute, "2023-01-01T00:02:00", &[1]);
+        misaligned.open_time = Time::from_str("2023-01-01T00:02:30").unwrap();
+        assert!(matches!(
+            resampler.push(misaligned),
+            Err(Error::MisalignedTime { .. })
+        ));
+    }
+}
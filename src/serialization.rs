@@ -0,0 +1,248 @@
+//! `serde` support, enabled by the `serde` feature.
+//!
+//! `Price`, `Quantity` and `Volume` are decimal strings keeping their scale, e.g. `"0.00012340"`,
+//! `Interval` is its code, e.g. `"15m"`, and `Time` is an RFC 3339 string unless a field picks
+//! one of the `time` representations:
+//!
+//! ```
+//! use candlestick::Time;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Kline {
+//!     #[serde(with = "candlestick::serialization::time::millis")]
+//!     open_time: Time,
+//! }
+//!
+//! let kline = Kline {
+//!     open_time: Time::from_millis(1_672_531_200_000),
+//! };
+//! let json = serde_json::to_string(&kline).unwrap();
+//! assert_eq!(json, r#"{"open_time":1672531200000}"#);
+//! ```
+//!
+//! `seconds` and `millis` round times with a finer fraction toward negative infinity,
+//! like `Time::value` and `Time::as_millis`.
+use crate::models::{Interval, Time};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Interval::from_str(&value).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Time {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        time::rfc3339::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Time {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        time::rfc3339::deserialize(deserializer)
+    }
+}
+
+/// `Time` representations for `#[serde(with = "...")]`.
+pub mod time {
+    use super::*;
+
+    /// RFC 3339 string in UTC, the default.
+    pub mod rfc3339 {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(time)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+            let value = String::deserialize(deserializer)?;
+            Time::from_str(&value).map_err(de::Error::custom)
+        }
+    }
+
+    /// seconds since the unix epoch, rounded toward negative infinity.
+    pub mod seconds {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_i64(time.value())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+            Time::checked_from_seconds(i64::deserialize(deserializer)?).map_err(de::Error::custom)
+        }
+    }
+
+    /// milliseconds since the unix epoch, rounded toward negative infinity.
+    pub mod millis {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_i64(time.as_millis())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+            Time::checked_from_millis(i64::deserialize(deserializer)?).map_err(de::Error::custom)
+        }
+    }
+
+    /// nanoseconds since the unix epoch.
+    pub mod nanos {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(time: &Time, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_i64(time.as_nanos())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+            i64::deserialize(deserializer).map(Time::from_nanos)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{Candlestick, CandlestickData, Duration, Price, Quantity};
+    use crate::test_fixtures::asset_pair;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Times {
+        rfc3339: Time,
+        #[serde(with = "time::seconds")]
+        seconds: Time,
+        #[serde(with = "time::millis")]
+        millis: Time,
+        #[serde(with = "time::nanos")]
+        nanos: Time,
+    }
+
+    #[test]
+    fn test_fixed_point() {
+        let price = Price::new(12340, 8);
+        let json = serde_json::to_string(&price).unwrap();
+        assert_eq!(json, r#""0.00012340""#);
+        let actual: Price = serde_json::from_str(&json).unwrap();
+        assert_eq!((actual.num, actual.scale), (12340, 8));
+
+        let quantity: Quantity = serde_json::from_str(r#""150.0""#).unwrap();
+        assert_eq!((quantity.num, quantity.scale), (1500, 1));
+        assert!(serde_json::from_str::<Quantity>(r#""-1""#).is_err());
+        assert!(serde_json::from_str::<Quantity>("1.5").is_err());
+    }
+
+    #[test]
+    fn test_interval() {
+        let intervals = vec![
+            (Interval::FifteenMinutes, r#""15m""#),
+            (Interval::OneMonth, r#""1M""#),
+            (
+                Interval::custom(Duration::from_seconds(90)).unwrap(),
+                r#""90s""#,
+            ),
+        ];
+        for (interval, json) in intervals {
+            assert_eq!(serde_json::to_string(&interval).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Interval>(json).unwrap(), interval);
+        }
+        assert!(serde_json::from_str::<Interval>(r#""15x""#).is_err());
+    }
+
+    #[test]
+    fn test_time() {
+        let time = Time::from_str("2023-01-01T00:00:01Z").unwrap();
+        let times = Times {
+            rfc3339: time,
+            seconds: time,
+            millis: time,
+            nanos: time,
+        };
+        let json = serde_json::to_string(&times).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rfc3339":"2023-01-01T00:00:01Z","seconds":1672531201,"millis":1672531201000,"nanos":1672531201000000000}"#
+        );
+        assert_eq!(serde_json::from_str::<Times>(&json).unwrap(), times);
+
+        let subsec = Time::from_nanos(1_672_531_201_500_250_000);
+        let times = Times {
+            rfc3339: subsec,
+            seconds: subsec,
+            millis: subsec,
+            nanos: subsec,
+        };
+        let json = serde_json::to_string(&times).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rfc3339":"2023-01-01T00:00:01.500250Z","seconds":1672531201,"millis":1672531201500,"nanos":1672531201500250000}"#
+        );
+        let actual = serde_json::from_str::<Times>(&json).unwrap();
+        assert_eq!((actual.rfc3339, actual.nanos), (subsec, subsec));
+        assert_eq!(actual.seconds, time);
+        assert_eq!(actual.millis, Time::from_millis(1_672_531_201_500));
+
+        let before_epoch: Times = serde_json::from_str(
+            r#"{"rfc3339":"1969-12-31T23:59:59.5Z","seconds":-1,"millis":-500,"nanos":-500000000}"#,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&Times {
+            seconds: before_epoch.nanos,
+            ..before_epoch
+        })
+        .unwrap();
+        assert!(json.contains(r#""seconds":-1,"millis":-500"#));
+    }
+
+    #[test]
+    fn test_sub_second_seconds() {
+        #[derive(Serialize)]
+        struct Seconds(#[serde(with = "time::seconds")] Time);
+
+        let tests = vec![
+            (Time::from_nanos(1_672_531_201_999_999_999), "1672531201"),
+            (Time::from_nanos(1), "0"),
+            (Time::from_nanos(-1), "-1"),
+            (Time::from_millis(-1_500), "-2"),
+        ];
+        for (time, json) in tests {
+            assert_eq!(serde_json::to_string(&Seconds(time)).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_candlestick() {
+        let mut data =
+            CandlestickData::new_with_price_and_quantity(Price::new(1000, 2), Quantity::new(5, 1))
+                .unwrap();
+        data.add_price_and_quantity(Price::new(1250, 2), Quantity::new(15, 1))
+            .unwrap();
+        let candlestick = Candlestick::new_with_data(
+            asset_pair(),
+            Time::from_str("2023-01-01T00:00:00Z").unwrap(),
+            Interval::OneHour,
+            Some(data),
+        );
+        let json = serde_json::to_string(&candlestick).unwrap();
+        let actual: Candlestick<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&actual).unwrap(), json);
+        assert_eq!(actual.asset_pair, candlestick.asset_pair);
+        assert_eq!(actual.open_time, candlestick.open_time);
+        assert_eq!(actual.interval, Interval::OneHour);
+        let (expected, actual) = (candlestick.data.unwrap(), actual.data.unwrap());
+        assert_eq!(
+            (actual.high.num, actual.high.scale),
+            (expected.high.num, expected.high.scale)
+        );
+        assert!(json.contains(r#""quote_volume":"23.750""#));
+    }
+}
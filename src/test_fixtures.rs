@@ -0,0 +1,30 @@
+//! fixtures shared by the unit tests.
+use crate::models::{AssetPair, OrderFilled, Price, Quantity, Time};
+
+/// `BTC/USD`.
+pub fn asset_pair() -> AssetPair<String> {
+    asset_pair_for("BTC")
+}
+
+/// `base_asset` quoted in `USD`.
+pub fn asset_pair_for(base_asset: &str) -> AssetPair<String> {
+    AssetPair {
+        base_asset: base_asset.to_owned(),
+        quote_asset: "USD".to_owned(),
+    }
+}
+
+/// one `BTC/USD` unit filled at a whole `price`, `time` seconds after the epoch.
+pub fn order_filled(price: u64, time: i64) -> OrderFilled<String> {
+    order_filled_for("BTC", price, time)
+}
+
+/// as `order_filled`, for `base_asset` quoted in `USD`.
+pub fn order_filled_for(base_asset: &str, price: u64, time: i64) -> OrderFilled<String> {
+    OrderFilled::new(
+        asset_pair_for(base_asset),
+        Price::new(price, 0),
+        Quantity::new(1, 0),
+        Time::new(time),
+    )
+}